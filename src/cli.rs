@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use image::ImageFormat;
+use rust_raytracing::raytracing::{
+    render::{RenderSettings, Renderer, BACKUP_FILENAME, TILE_SIZE},
+    sampler::SamplerKind,
+    tile::TileOrder,
+    tonemap::{ToneMapOperator, ToneMapper},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BuiltinScene {
+    /// Three spheres on a ground plane
+    Simple,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Tga,
+    Tiff,
+    Ppm,
+    /// OpenEXR, linear 32-bit float
+    Exr,
+    /// Radiance RGBE, linear HDR
+    Hdr,
+}
+
+impl OutputFormat {
+    pub fn image_format(self) -> ImageFormat {
+        match self {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Bmp => ImageFormat::Bmp,
+            OutputFormat::Tga => ImageFormat::Tga,
+            OutputFormat::Tiff => ImageFormat::Tiff,
+            OutputFormat::Ppm => ImageFormat::Pnm,
+            OutputFormat::Exr => ImageFormat::OpenExr,
+            OutputFormat::Hdr => ImageFormat::Hdr,
+        }
+    }
+}
+
+/// Ray tracing renderer
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Scene description file (TOML)
+    #[arg(short, long, conflicts_with = "builtin")]
+    pub scene: Option<PathBuf>,
+
+    /// Built-in scene rendered when no scene file is given
+    #[arg(short, long, value_enum, default_value_t = BuiltinScene::Simple)]
+    pub builtin: BuiltinScene,
+
+    /// Image width in pixels, overrides the scene
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: Option<u32>,
+
+    /// Image height in pixels, overrides the scene
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub height: Option<u32>,
+
+    /// Samples per pixel, overrides the scene
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub spp: Option<u32>,
+
+    /// Sampler (random, stratified, halton, sobol), overrides the scene
+    #[arg(long)]
+    pub sampler: Option<SamplerKind>,
+
+    /// Maximum number of bounces, overrides the scene
+    #[arg(long)]
+    pub max_depth: Option<usize>,
+
+    /// Number of render threads [default: all cores]
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+
+    /// Edge length of a render tile in pixels
+    #[arg(long, default_value_t = TILE_SIZE, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tile_size: u32,
+
+    /// Tile order (scanline, spiral, hilbert)
+    #[arg(long, default_value = "spiral")]
+    pub tile_order: TileOrder,
+
+    /// Random seed, the same seed renders the same image
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Output image path
+    #[arg(short, long, default_value = BACKUP_FILENAME)]
+    pub output: PathBuf,
+
+    /// Output format [default: guessed from the output extension]
+    #[arg(short, long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Exposure adjustment in stops applied before tone mapping
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub exposure: f64,
+
+    /// Tone mapping operator for 8-bit output (clamp, reinhard, hable, aces)
+    #[arg(long, default_value = "aces")]
+    pub tonemap: ToneMapOperator,
+
+    /// Disable dithering when quantizing to 8 bits
+    #[arg(long)]
+    pub no_dither: bool,
+
+    /// Do not open a window showing the result
+    #[arg(long)]
+    pub no_window: bool,
+}
+
+impl Args {
+    /// Apply the command line overrides to the settings of a scene
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        if let Some(width) = self.width {
+            settings.width = width;
+        }
+        if let Some(height) = self.height {
+            settings.height = height;
+        }
+        if let Some(spp) = self.spp {
+            settings.spp = spp;
+        }
+        if let Some(sampler) = self.sampler {
+            settings.sampler = sampler;
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+    }
+
+    pub fn tone_mapper(&self) -> ToneMapper {
+        ToneMapper::new(self.tonemap)
+            .exposure(self.exposure)
+            .dither(!self.no_dither)
+    }
+
+    pub fn renderer(&self) -> Renderer {
+        let mut renderer = Renderer::new()
+            .tile_size(self.tile_size)
+            .tile_order(self.tile_order)
+            .seed(self.seed);
+        if let Some(threads) = self.threads {
+            renderer = renderer.threads(threads);
+        }
+        renderer
+    }
+}
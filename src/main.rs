@@ -1,11 +1,16 @@
-mod raytracing;
+mod cli;
 mod window;
 
+use std::{error::Error, process::ExitCode};
+
+use clap::Parser;
+use image::ImageFormat;
+use rust_raytracing::raytracing::{
+    render::Scene, scene_file::FileScene, simple_scene::SimpleScene,
+};
+
 use crate::{
-    raytracing::{
-        render::{render, Scene},
-        simple_scene::SimpleScene,
-    },
+    cli::{Args, BuiltinScene},
     window::Draw,
 };
 
@@ -35,13 +40,57 @@ use crate::{
 //     Color::new(1.0, 1.0, 1.0)
 // }
 
-fn main() {
-    println!("Hello! Ray tracing world!");
+fn run(scene: &(impl Scene + Sync), args: &Args) -> Result<(), Box<dyn Error>> {
+    let format = match args.format {
+        Some(format) => format.image_format(),
+        None => ImageFormat::from_path(&args.output)
+            .map_err(|_| format!("cannot guess the format of {}", args.output.display()))?,
+    };
+
+    println!(
+        "Rendering {}x{} at {} spp",
+        scene.width(),
+        scene.height(),
+        scene.spp()
+    );
+    let film = args.renderer().render(scene);
+
+    let tone_mapper = args.tone_mapper();
+    film.save(&args.output, format, &tone_mapper)?;
+    println!("Saved {}", args.output.display());
 
-    let img = render(SimpleScene::new());
+    if !args.no_window {
+        let drawer = Draw::new();
+        drawer.setup_window(film.to_rgb_image(&tone_mapper))?;
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    println!("Hello! Ray tracing world!");
 
-    img.save(String::from("render.png")).unwrap();
+    let result = match (&args.scene, args.builtin) {
+        (Some(path), _) => FileScene::load(path)
+            .map_err(Box::from)
+            .and_then(|mut scene| {
+                args.apply(&mut scene.settings);
+                run(&scene, &args)
+            }),
+        (None, BuiltinScene::Simple) => {
+            let mut scene = SimpleScene::new();
+            args.apply(&mut scene.settings);
+            run(&scene, &args)
+        }
+    };
 
-    let drawer = Draw::new();
-    drawer.setup_window(img).unwrap();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
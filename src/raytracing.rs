@@ -1,11 +1,33 @@
+pub mod aabb;
+pub mod bvh;
 pub mod camera;
+pub mod csg;
+pub mod environment;
+pub mod film;
 pub mod float3;
 pub mod hit_info;
+pub mod light;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
+pub mod obj;
+pub mod primitives;
+pub mod principled;
 pub mod quaternion;
 pub mod ray;
 pub mod render;
+pub mod rng;
+pub mod sampler;
+pub mod scene_file;
+pub mod sdf;
 pub mod shapes;
 pub mod simple_scene;
+pub mod subsurface;
+pub mod texture;
+pub mod tile;
+pub mod tonemap;
+pub mod transform;
 
 pub use self::float3::{Color, Float3, Point3, Vec3};
 pub use std::f64::consts::FRAC_1_PI;
@@ -0,0 +1,103 @@
+use super::{ray::Ray, Point3};
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub const fn new(min: Point3, max: Point3) -> Self {
+        Self { min, max }
+    }
+
+    /// Construct a box containing nothing, the identity of `union`
+    pub const fn empty() -> Self {
+        Self {
+            min: Point3::full(f64::INFINITY),
+            max: Point3::full(f64::NEG_INFINITY),
+        }
+    }
+
+    /// Construct the smallest box containing all points
+    pub fn from_points(points: impl IntoIterator<Item = Point3>) -> Self {
+        points
+            .into_iter()
+            .fold(Self::empty(), |aabb, p| aabb.grow(p))
+    }
+
+    /// Compute the smallest box containing both boxes
+    pub fn union(&self, rhs: Self) -> Self {
+        Self::new(self.min.min(rhs.min), self.max.max(rhs.max))
+    }
+
+    /// Compute the box both boxes share, empty if they do not overlap
+    pub fn intersection(&self, rhs: Self) -> Self {
+        Self::new(self.min.max(rhs.min), self.max.min(rhs.max))
+    }
+
+    /// Compute the smallest box containing this box and a point
+    pub fn grow(&self, p: Point3) -> Self {
+        Self::new(self.min.min(p), self.max.max(p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    pub fn extent(&self) -> Point3 {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Compute the surface area, used by the surface area heuristic
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.extent();
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    /// Returns the index of the longest axis
+    pub fn longest_axis(&self) -> usize {
+        let d = self.extent();
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test, returns whether the ray enters the box within [t0, t1]
+    pub fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> bool {
+        self.clip(ray, t0, t1).is_some()
+    }
+
+    /// Returns the part of [t0, t1] where the ray is inside the box
+    pub fn clip(&self, ray: &Ray, t0: f64, t1: f64) -> Option<(f64, f64)> {
+        let mut t_min = t0;
+        let mut t_max = t1;
+        for axis in 0..3 {
+            let inv_d = ray.direction[axis].recip();
+            let mut near = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut far = (self.max[axis] - ray.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut near, &mut far);
+            }
+            // NaN (0 * inf) のときは区間を狭めない
+            t_min = if near > t_min { near } else { t_min };
+            t_max = if far < t_max { far } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
@@ -0,0 +1,261 @@
+use super::{
+    aabb::Aabb,
+    hit_info::HitInfo,
+    ray::Ray,
+    shapes::{Shape, ShapeList},
+    Point3,
+};
+
+/// Number of buckets the centroids are binned into when evaluating splits
+const SAH_BUCKETS: usize = 12;
+/// Maximum number of shapes in a leaf
+const MAX_LEAF_SIZE: usize = 4;
+/// Relative cost of traversing a node compared to intersecting a shape
+const TRAVERSAL_COST: f64 = 0.125;
+
+enum BvhNodeKind {
+    /// Shapes `first..first + count` of the reordered shape list
+    Leaf { first: usize, count: usize },
+    /// The first child directly follows its parent, the second one is at `second`
+    Interior { second: usize, axis: usize },
+}
+
+struct BvhNode {
+    bounds: Aabb,
+    kind: BvhNodeKind,
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point3,
+}
+
+/// Bounding volume hierarchy built with the surface area heuristic.
+///
+/// Drop-in replacement for `ShapeList`; unbounded shapes are kept aside and tested linearly.
+/// Hits report the position of the shape in the original list as their `shape_id`.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    /// Shapes in the order the leaves reference them, with their original index
+    objects: Vec<(usize, Box<dyn Shape>)>,
+    unbounded: Vec<(usize, Box<dyn Shape>)>,
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Box<dyn Shape>>) -> Self {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut items = Vec::new();
+        for (id, object) in objects.into_iter().enumerate() {
+            match object.bounding_box() {
+                Some(bounds) => {
+                    items.push(BuildItem {
+                        index: bounded.len(),
+                        bounds,
+                        centroid: bounds.centroid(),
+                    });
+                    bounded.push(Some((id, object)));
+                }
+                None => unbounded.push((id, object)),
+            }
+        }
+
+        let mut nodes = Vec::with_capacity(2 * items.len());
+        if !items.is_empty() {
+            Self::build(&mut nodes, &mut items, 0);
+        }
+
+        // 葉が参照する順番に形状を並べ替える
+        let objects = items
+            .iter()
+            .map(|item| bounded[item.index].take().unwrap())
+            .collect();
+
+        Self {
+            nodes,
+            objects,
+            unbounded,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len() + self.unbounded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn build(nodes: &mut Vec<BvhNode>, items: &mut [BuildItem], first: usize) -> usize {
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |aabb, item| aabb.union(item.bounds));
+        let node_index = nodes.len();
+        nodes.push(BvhNode {
+            bounds,
+            kind: BvhNodeKind::Leaf {
+                first,
+                count: items.len(),
+            },
+        });
+
+        if items.len() <= 1 {
+            return node_index;
+        }
+
+        let centroid_bounds = Aabb::from_points(items.iter().map(|item| item.centroid));
+        let axis = centroid_bounds.longest_axis();
+        let (lo, hi) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+        if hi - lo <= 0.0 {
+            // 重心がすべて重なっている場合は分割しても意味がない
+            if items.len() <= MAX_LEAF_SIZE {
+                return node_index;
+            }
+            let mid = items.len() / 2;
+            return Self::split(nodes, items, first, node_index, mid, axis);
+        }
+
+        let bucket_of = |item: &BuildItem| {
+            let b = ((item.centroid[axis] - lo) / (hi - lo) * SAH_BUCKETS as f64) as usize;
+            b.min(SAH_BUCKETS - 1)
+        };
+
+        let mut buckets = [(0usize, Aabb::empty()); SAH_BUCKETS];
+        for item in items.iter() {
+            let bucket = &mut buckets[bucket_of(item)];
+            bucket.0 += 1;
+            bucket.1 = bucket.1.union(item.bounds);
+        }
+
+        // 各分割位置での SAH コストを左右からの累積で求める
+        let mut costs = [0.0; SAH_BUCKETS - 1];
+        let (mut count, mut aabb) = (0, Aabb::empty());
+        for (i, cost) in costs.iter_mut().enumerate() {
+            count += buckets[i].0;
+            aabb = aabb.union(buckets[i].1);
+            *cost = count as f64 * aabb.surface_area();
+        }
+        let (mut count, mut aabb) = (0, Aabb::empty());
+        for i in (1..SAH_BUCKETS).rev() {
+            count += buckets[i].0;
+            aabb = aabb.union(buckets[i].1);
+            costs[i - 1] += count as f64 * aabb.surface_area();
+        }
+
+        let (split_bucket, min_cost) =
+            costs
+                .iter()
+                .enumerate()
+                .fold(
+                    (0, f64::INFINITY),
+                    |best, (i, &c)| if c < best.1 { (i, c) } else { best },
+                );
+        let split_cost = TRAVERSAL_COST + min_cost / bounds.surface_area().max(f64::MIN_POSITIVE);
+        let leaf_cost = items.len() as f64;
+
+        if items.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+            return node_index;
+        }
+
+        let mut mid = partition(items, |item| bucket_of(item) <= split_bucket);
+        if mid == 0 || mid == items.len() {
+            // バケットで分けられなかったときは重心の中央値で半分に分ける
+            mid = items.len() / 2;
+            items.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        }
+        Self::split(nodes, items, first, node_index, mid, axis)
+    }
+
+    fn split(
+        nodes: &mut Vec<BvhNode>,
+        items: &mut [BuildItem],
+        first: usize,
+        node_index: usize,
+        mid: usize,
+        axis: usize,
+    ) -> usize {
+        let (left, right) = items.split_at_mut(mid);
+        Self::build(nodes, left, first);
+        let second = Self::build(nodes, right, first + mid);
+        nodes[node_index].kind = BvhNodeKind::Interior { second, axis };
+        node_index
+    }
+}
+
+/// Moves the items satisfying `pred` to the front, returns their count
+fn partition(items: &mut [BuildItem], pred: impl Fn(&BuildItem) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+impl From<ShapeList> for Bvh {
+    fn from(list: ShapeList) -> Self {
+        Self::new(list.objects)
+    }
+}
+
+impl Shape for Bvh {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let mut hit_info: Option<HitInfo> = None;
+        let mut closest_so_far = t1;
+
+        for (id, object) in &self.unbounded {
+            if let Some(mut info) = object.hit(ray, t0, closest_so_far) {
+                closest_so_far = info.length;
+                info.shape_id = Some(*id);
+                hit_info = Some(info);
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return hit_info;
+        }
+
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds.hit(ray, t0, closest_so_far) {
+                continue;
+            }
+            match node.kind {
+                BvhNodeKind::Leaf { first, count } => {
+                    for (id, object) in &self.objects[first..first + count] {
+                        if let Some(mut info) = object.hit(ray, t0, closest_so_far) {
+                            closest_so_far = info.length;
+                            info.shape_id = Some(*id);
+                            hit_info = Some(info);
+                        }
+                    }
+                }
+                BvhNodeKind::Interior { second, axis } => {
+                    // レイの向きに近い側の子を先に調べる
+                    if ray.direction[axis] < 0.0 {
+                        stack.push(index + 1);
+                        stack.push(second);
+                    } else {
+                        stack.push(second);
+                        stack.push(index + 1);
+                    }
+                }
+            }
+        }
+
+        hit_info
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        // 空のときも無限に広がっているわけではないので空の箱を返す
+        Some(self.nodes.first().map_or(Aabb::empty(), |node| node.bounds))
+    }
+}
@@ -1,19 +1,105 @@
-use super::{ray::Ray, Color, Point3, Vec3};
+use serde::Deserialize;
 
-pub struct Camera {
+use super::{ray::Ray, render::World, Point3, Vec3, EPS, PI, PI2};
+
+/// Shape of the lens opening, which is also the shape of out-of-focus highlights (bokeh)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Aperture {
+    #[default]
+    Circle,
+    /// Regular polygon formed by `blades` diaphragm blades, rotated by `rotation` degrees
+    Polygon { blades: u32, rotation: f64 },
+}
+
+impl Aperture {
+    /// Map a uniform sample in [0..1)^2 to a uniformly distributed point inside the unit aperture
+    pub fn sample(&self, (s, t): (f64, f64)) -> (f64, f64) {
+        match *self {
+            Aperture::Circle => concentric_disk(s, t),
+            Aperture::Polygon { blades, rotation } => {
+                let blades = blades.max(3);
+                // どの三角形に入るかを s で選び、残りの端数を再利用する
+                let scaled = s * blades as f64;
+                let index = (scaled as u32).min(blades - 1);
+                let s = scaled - index as f64;
+
+                // 中心と隣り合う頂点2つからなる三角形の中で一様にサンプルする
+                let angle = PI2 / blades as f64;
+                let theta = rotation.to_radians() + PI * 0.5 + angle * index as f64;
+                let (a, b) = (
+                    (theta.cos(), theta.sin()),
+                    ((theta + angle).cos(), (theta + angle).sin()),
+                );
+                let sqrt_s = s.sqrt();
+                let (wa, wb) = (sqrt_s * (1.0 - t), sqrt_s * t);
+                (a.0 * wa + b.0 * wb, a.1 * wa + b.1 * wb)
+            }
+        }
+    }
+}
+
+/// Shirley-Chiu concentric mapping from the unit square to the unit disk
+fn concentric_disk(s: f64, t: f64) -> (f64, f64) {
+    let (a, b) = (2.0 * s - 1.0, 2.0 * t - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, phi) = if a.abs() > b.abs() {
+        (a, PI * 0.25 * (b / a))
+    } else {
+        (b, PI * 0.5 - PI * 0.25 * (a / b))
+    };
+    (r * phi.cos(), r * phi.sin())
+}
+
+/// Maps a position on the image to a primary ray
+pub trait Camera: Sync {
+    /// Returns the ray through screen position (u, v) in [0..1]^2 with v pointing up, `lens`
+    /// is a 2D sample for the aperture. `None` means the position is outside the image
+    /// circle and stays black
+    fn ray(&self, u: f64, v: f64, lens: (f64, f64)) -> Option<Ray>;
+}
+
+/// Orthonormal frame of a camera looking from `origin` towards `lookat`
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    right: Vec3,
+    up: Vec3,
+    forward: Vec3,
+}
+
+impl Frame {
+    fn from_lookat(origin: Point3, lookat: Point3, camera_up: Vec3) -> Self {
+        let forward = (lookat - origin).normalize();
+        let right = forward.cross(camera_up).normalize();
+        Self {
+            right,
+            up: right.cross(forward),
+            forward,
+        }
+    }
+}
+
+/// Pinhole or thin lens camera with a perspective projection
+pub struct PerspectiveCamera {
     pub origin: Point3,
     pub u: Vec3,
     pub v: Vec3,
     pub w: Vec3,
+    /// Radius of the lens, zero is a pinhole with everything in focus
+    pub lens_radius: f64,
+    pub aperture: Aperture,
 }
 
-impl Camera {
+impl PerspectiveCamera {
     pub fn new(u: Vec3, v: Vec3, w: Vec3) -> Self {
         Self {
             origin: Point3::zero(),
             u, // x
             v, // y
             w, // z
+            lens_radius: 0.0,
+            aperture: Aperture::Circle,
         }
     }
 
@@ -41,17 +127,242 @@ impl Camera {
         let vh = v * half_h;
 
         Self {
-            origin: origin,
+            origin,
             u: uw * 2.0,
             v: vh * 2.0,
             w: origin - uw - vh - w, // 原点位置から引いていくことでz軸方向のベクトル(位置)が出る
+            lens_radius: 0.0,
+            aperture: Aperture::Circle,
         }
     }
 
-    pub fn ray(&self, u: f64, v: f64) -> Ray {
-        Ray {
+    /// Turn the pinhole into a thin lens of the given radius focused at `focus_distance`
+    pub fn with_lens(self, lens_radius: f64, focus_distance: f64) -> Self {
+        Self {
+            lens_radius: lens_radius.max(0.0),
+            ..self
+        }
+        .with_focus_distance(focus_distance)
+    }
+
+    pub fn with_aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    /// Move the plane of focus, the field of view stays the same
+    pub fn with_focus_distance(self, focus_distance: f64) -> Self {
+        // スクリーンをピント面まで相似に拡大する
+        let scale = focus_distance.max(EPS) / self.focus_distance();
+        Self {
+            u: self.u * scale,
+            v: self.v * scale,
+            w: self.origin + (self.w - self.origin) * scale,
+            ..self
+        }
+    }
+
+    /// Distance from the lens to the plane of focus along the view direction
+    pub fn focus_distance(&self) -> f64 {
+        self.center_direction().dot(self.forward())
+    }
+
+    /// Focus on whatever the center of the image sees, the focus is kept if nothing is hit
+    pub fn autofocus(self, world: &(impl World + ?Sized)) -> Self {
+        let mut ray = Ray {
             origin: self.origin,
-            direction: self.w + self.u * u + self.v * v - self.origin,
+            direction: self.forward(),
+        };
+        let mut distance = 0.0;
+        // 媒質の境界は見えないので通り抜ける
+        while let Some(hit) = world.hit(&ray, EPS, f64::MAX) {
+            distance += hit.length;
+            if !hit.material.is_interface() {
+                return self.with_focus_distance(distance);
+            }
+            ray.origin = hit.position;
+        }
+        self
+    }
+
+    fn center_direction(&self) -> Vec3 {
+        self.w + self.u * 0.5 + self.v * 0.5 - self.origin
+    }
+
+    fn forward(&self) -> Vec3 {
+        -self.u.cross(self.v).normalize()
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn ray(&self, u: f64, v: f64, lens: (f64, f64)) -> Option<Ray> {
+        let target = self.w + self.u * u + self.v * v;
+        if self.lens_radius <= 0.0 {
+            return Some(Ray {
+                origin: self.origin,
+                direction: target - self.origin,
+            });
         }
+
+        // レンズ上の点から、ピント面上の同じ点に向けてレイを飛ばす
+        let (lx, ly) = self.aperture.sample(lens);
+        let offset = self.u.normalize() * (lx * self.lens_radius)
+            + self.v.normalize() * (ly * self.lens_radius);
+        let origin = self.origin + offset;
+        Some(Ray {
+            origin,
+            direction: target - origin,
+        })
+    }
+}
+
+/// Parallel projection for technical views, all rays share the view direction
+pub struct OrthographicCamera {
+    /// Lower left corner of the view rectangle
+    corner: Point3,
+    u: Vec3,
+    v: Vec3,
+    direction: Vec3,
+}
+
+impl OrthographicCamera {
+    /// `height` is the size of the visible area in world units
+    pub fn from_lookat(
+        origin: Point3,
+        lookat: Point3,
+        camera_up: Vec3,
+        height: f64,
+        aspect: f64,
+    ) -> Self {
+        let frame = Frame::from_lookat(origin, lookat, camera_up);
+        let u = frame.right * (height * aspect);
+        let v = frame.up * height;
+        Self {
+            corner: origin - u * 0.5 - v * 0.5,
+            u,
+            v,
+            direction: frame.forward,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn ray(&self, u: f64, v: f64, _lens: (f64, f64)) -> Option<Ray> {
+        Some(Ray {
+            origin: self.corner + self.u * u + self.v * v,
+            direction: self.direction,
+        })
+    }
+}
+
+/// How a fisheye lens maps the angle from the optical axis to the distance from the image center
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FisheyeMapping {
+    /// Distance proportional to the angle
+    #[default]
+    Equidistant,
+    /// Equal solid angles cover equal image areas
+    Equisolid,
+}
+
+impl std::str::FromStr for FisheyeMapping {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "equidistant" => Ok(FisheyeMapping::Equidistant),
+            "equisolid" => Ok(FisheyeMapping::Equisolid),
+            _ => Err(format!(
+                "unknown fisheye mapping `{}`, expected equidistant or equisolid",
+                s
+            )),
+        }
+    }
+}
+
+/// Fisheye lens with a circular image inscribed in the image height
+pub struct FisheyeCamera {
+    origin: Point3,
+    frame: Frame,
+    /// Half of the field of view covered by the image circle, in radians
+    half_fov: f64,
+    mapping: FisheyeMapping,
+    aspect: f64,
+}
+
+impl FisheyeCamera {
+    /// `fov` is the angle in degrees across the diameter of the image circle, up to 360
+    pub fn from_lookat(
+        origin: Point3,
+        lookat: Point3,
+        camera_up: Vec3,
+        fov: f64,
+        mapping: FisheyeMapping,
+        aspect: f64,
+    ) -> Self {
+        Self {
+            origin,
+            frame: Frame::from_lookat(origin, lookat, camera_up),
+            half_fov: fov.clamp(EPS, 360.0).to_radians() * 0.5,
+            mapping,
+            aspect,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn ray(&self, u: f64, v: f64, _lens: (f64, f64)) -> Option<Ray> {
+        // 画像の中心を原点として、縦方向が [-1..1] になる座標に直す
+        let x = (2.0 * u - 1.0) * self.aspect;
+        let y = 2.0 * v - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        // 像の中心からの距離を光軸からの角度に変換する
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => {
+                2.0 * (r * (self.half_fov * 0.5).sin()).clamp(-1.0, 1.0).asin()
+            }
+        };
+        let phi = y.atan2(x);
+        let Frame { right, up, forward } = self.frame;
+        let direction = forward * theta.cos() + (right * phi.cos() + up * phi.sin()) * theta.sin();
+        Some(Ray {
+            origin: self.origin,
+            direction,
+        })
+    }
+}
+
+/// Full 360x180 degree latitude-longitude panorama, the center of the image looks at `lookat`
+pub struct EquirectangularCamera {
+    origin: Point3,
+    frame: Frame,
+}
+
+impl EquirectangularCamera {
+    pub fn from_lookat(origin: Point3, lookat: Point3, camera_up: Vec3) -> Self {
+        Self {
+            origin,
+            frame: Frame::from_lookat(origin, lookat, camera_up),
+        }
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn ray(&self, u: f64, v: f64, _lens: (f64, f64)) -> Option<Ray> {
+        // 横方向を経度 [-π..π]、縦方向を緯度 [-π/2..π/2] とする
+        let longitude = (u - 0.5) * PI2;
+        let latitude = (v - 0.5) * PI;
+        let Frame { right, up, forward } = self.frame;
+        let direction = (forward * longitude.cos() + right * longitude.sin()) * latitude.cos()
+            + up * latitude.sin();
+        Some(Ray {
+            origin: self.origin,
+            direction,
+        })
     }
 }
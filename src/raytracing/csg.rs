@@ -0,0 +1,203 @@
+//! Constructive solid geometry on ray intervals.
+//!
+//! A solid reports the spans of a ray inside it, a `Csg` node combines the spans of its two
+//! children and reports the first boundary of the result as its hit.
+
+use super::{aabb::Aabb, hit_info::HitInfo, ray::Ray, shapes::Shape};
+
+/// Where a ray enters or leaves a solid
+pub struct Boundary {
+    pub length: f64,
+    /// None where the span is cut by the ends of the searched range
+    pub hit: Option<HitInfo>,
+}
+
+/// Part of a ray inside a solid
+pub struct Span {
+    pub enter: Boundary,
+    pub exit: Boundary,
+}
+
+/// Spans of a closed shape within (t0, t1), found by following its hits along the ray.
+///
+/// The side of each hit tells whether the ray enters or leaves the shape, so this works for
+/// any shape whose normals point outward.
+pub fn spans_from_hits<S: Shape + ?Sized>(shape: &S, ray: &Ray, t0: f64, t1: f64) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut enter: Option<Boundary> = None;
+    let mut first = true;
+    let mut t = t0;
+    while let Some(hit) = shape.hit(ray, t, t1) {
+        t = hit.length;
+        match (enter.is_some(), hit.front_face) {
+            (false, true) => {
+                enter = Some(Boundary {
+                    length: t,
+                    hit: Some(hit),
+                })
+            }
+            (true, false) => spans.push(Span {
+                enter: enter.take().unwrap(),
+                exit: Boundary {
+                    length: t,
+                    hit: Some(hit),
+                },
+            }),
+            // 最初に裏から当たったならレイの始点は内側
+            (false, false) if first => spans.push(Span {
+                enter: Boundary {
+                    length: t0,
+                    hit: None,
+                },
+                exit: Boundary {
+                    length: t,
+                    hit: Some(hit),
+                },
+            }),
+            // 共有された辺で二重に当たった場合などは無視する
+            _ => {}
+        }
+        first = false;
+    }
+    if let Some(enter) = enter {
+        spans.push(Span {
+            enter,
+            exit: Boundary {
+                length: t1,
+                hit: None,
+            },
+        });
+    }
+    spans
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// The left shape with the right one cut away
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(self, left: bool, right: bool) -> bool {
+        match self {
+            CsgOperation::Union => left || right,
+            CsgOperation::Intersection => left && right,
+            CsgOperation::Difference => left && !right,
+        }
+    }
+}
+
+/// Boolean combination of two solids, which may be `Csg` nodes themselves.
+///
+/// Each part of the surface keeps the material of the child it came from.
+pub struct Csg {
+    operation: CsgOperation,
+    left: Box<dyn Shape>,
+    right: Box<dyn Shape>,
+    bounds: Option<Aabb>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Box<dyn Shape>, right: Box<dyn Shape>) -> Self {
+        let (a, b) = (left.bounding_box(), right.bounding_box());
+        let bounds = match operation {
+            CsgOperation::Union => a.zip(b).map(|(a, b)| a.union(b)),
+            CsgOperation::Intersection => match (a, b) {
+                (Some(a), Some(b)) => Some(a.intersection(b)),
+                (a, b) => a.or(b),
+            },
+            CsgOperation::Difference => a,
+        };
+        Self {
+            operation,
+            left,
+            right,
+            bounds,
+        }
+    }
+
+    pub fn union(left: Box<dyn Shape>, right: Box<dyn Shape>) -> Self {
+        Self::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Box<dyn Shape>, right: Box<dyn Shape>) -> Self {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Box<dyn Shape>, right: Box<dyn Shape>) -> Self {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+}
+
+impl Shape for Csg {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        self.intervals(ray, t0, t1)
+            .into_iter()
+            .flat_map(|span| [span.enter.hit, span.exit.hit])
+            .flatten()
+            .next()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    fn intervals(&self, ray: &Ray, t0: f64, t1: f64) -> Vec<Span> {
+        if let Some(bounds) = &self.bounds {
+            if !bounds.hit(ray, t0, t1) {
+                return Vec::new();
+            }
+        }
+        let left = self.left.intervals(ray, t0, t1);
+        // 結果が空になる場合は右側を調べない
+        if left.is_empty() && self.operation != CsgOperation::Union {
+            return left;
+        }
+        let right = self.right.intervals(ray, t0, t1);
+
+        // 両方の境界を距離順に並べ、内外の変化を追う
+        let mut events: Vec<(Boundary, bool, bool)> = Vec::new();
+        for (spans, is_left) in [(left, true), (right, false)] {
+            for span in spans {
+                events.push((span.enter, is_left, true));
+                events.push((span.exit, is_left, false));
+            }
+        }
+        events.sort_by(|a, b| a.0.length.total_cmp(&b.0.length));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut inside = false;
+        let mut enter: Option<Boundary> = None;
+        let mut spans = Vec::new();
+        for (mut boundary, is_left, entering) in events {
+            if is_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+            let now_inside = self.operation.inside(in_left, in_right);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+            // 子に入る境界が結果から出る境界になるときは法線を裏返す
+            if entering != now_inside {
+                if let Some(hit) = &mut boundary.hit {
+                    hit.direction = -hit.direction;
+                    hit.front_face = !hit.front_face;
+                }
+            }
+            if now_inside {
+                enter = Some(boundary);
+            } else if let Some(enter) = enter.take() {
+                spans.push(Span {
+                    enter,
+                    exit: boundary,
+                });
+            }
+        }
+        spans
+    }
+}
@@ -0,0 +1,278 @@
+use std::path::Path;
+
+use image::{ColorType, ImageResult};
+
+use super::{light::LightSample, quaternion::Quaternion, Color, Vec3, PI, PI2};
+
+/// Light arriving from infinitely far away, seen wherever rays leave the scene
+pub trait Environment: Sync + Send {
+    /// Returns the radiance arriving from `direction`
+    fn radiance(&self, direction: Vec3) -> Color;
+
+    /// True if the integrator should sample the environment like a light
+    fn is_sampled(&self) -> bool {
+        false
+    }
+
+    /// Sample a direction towards the environment, `u` is a uniform 2D sample
+    fn sample(&self, _u: (f64, f64)) -> Option<LightSample> {
+        None
+    }
+
+    /// Density in solid angle with which `sample` picks `direction`
+    fn pdf(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+}
+
+/// Environment with the same radiance in every direction
+pub struct ConstantEnvironment {
+    color: Color,
+}
+
+impl ConstantEnvironment {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for ConstantEnvironment {
+    fn radiance(&self, _direction: Vec3) -> Color {
+        self.color
+    }
+}
+
+/// Sky blending from `bottom` straight down to `top` straight up
+pub struct GradientEnvironment {
+    bottom: Color,
+    top: Color,
+}
+
+impl GradientEnvironment {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+}
+
+impl Environment for GradientEnvironment {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let t = 0.5 * (direction.normalize().y() + 1.0);
+        self.bottom.lerp(self.top, t)
+    }
+}
+
+/// Piecewise constant 1D distribution sampled by inverting its CDF
+struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    fn new(func: Vec<f64>) -> Self {
+        let n = func.len() as f64;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f / n);
+        }
+        let integral = cdf[func.len()];
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            // 全て0なら一様分布にする
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, c)| *c = i as f64 / n);
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    /// Returns a position in [0..1), its density and the index of its segment
+    fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let index = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.func.len() - 1);
+        let (start, end) = (self.cdf[index], self.cdf[index + 1]);
+        let offset = if end > start {
+            (u - start) / (end - start)
+        } else {
+            0.0
+        };
+        let x = (index as f64 + offset) / self.func.len() as f64;
+        (x, self.pdf(index), index)
+    }
+
+    fn pdf(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Latitude-longitude (equirectangular) environment map, importance sampled by luminance.
+///
+/// The center of the image is towards -z and the top row is straight up (+y).
+pub struct ImageEnvironment {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    /// Rotates directions from the map into the world
+    rotation: Quaternion,
+    intensity: f64,
+    /// Distribution over rows, and over the pixels of each row
+    marginal: Distribution1D,
+    conditional: Vec<Distribution1D>,
+}
+
+impl ImageEnvironment {
+    /// Build from linear pixels stored row by row
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        assert!(width > 0 && height > 0);
+
+        // 極付近の画素は立体角が小さいので sin θ で重み付けする
+        let conditional: Vec<_> = pixels
+            .chunks(width)
+            .enumerate()
+            .map(|(y, row)| {
+                let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+                Distribution1D::new(
+                    row.iter()
+                        .map(|c| c.luminance().max(0.0) * sin_theta)
+                        .collect(),
+                )
+            })
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral).collect());
+
+        Self {
+            width,
+            height,
+            pixels,
+            rotation: Quaternion::unit(),
+            intensity: 1.0,
+            marginal,
+            conditional,
+        }
+    }
+
+    /// Load an HDR or OpenEXR image, 8-bit images are decoded from sRGB
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<Self> {
+        let image = image::open(path)?;
+        let linear = matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+        let image = image.into_rgb32f();
+        let pixels = image
+            .pixels()
+            .map(|p| {
+                let color = Color::new(p[0] as f64, p[1] as f64, p[2] as f64);
+                if linear {
+                    color
+                } else {
+                    color.from_srgb()
+                }
+            })
+            .collect();
+        Ok(Self::new(
+            image.width() as usize,
+            image.height() as usize,
+            pixels,
+        ))
+    }
+
+    pub fn with_rotation(mut self, rotation: Quaternion) -> Self {
+        self.rotation = rotation.normalize();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Texture coordinates of a world space direction, v = 0 is the top row
+    fn direction_to_uv(&self, direction: Vec3) -> (f64, f64) {
+        let d = self.rotation.conj().rotate(direction).normalize();
+        let u = 0.5 + d.x().atan2(-d.z()) / PI2;
+        let v = d.y().clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let (sin_theta, cos_theta) = (v * PI).sin_cos();
+        let (sin_phi, cos_phi) = ((u - 0.5) * PI2).sin_cos();
+        self.rotation.rotate(Vec3::new(
+            sin_theta * sin_phi,
+            cos_theta,
+            -sin_theta * cos_phi,
+        ))
+    }
+
+    fn texel(&self, x: isize, y: isize) -> Color {
+        // 横方向は一周つながり、縦方向は端で止める
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    fn pixel_index(&self, u: f64, v: f64) -> (usize, usize) {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        (x, y)
+    }
+
+    /// Density over directions of the pixel at (x, y)
+    fn pixel_pdf(&self, x: usize, y: usize, v: f64) -> f64 {
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // 画像上の密度を、緯度経度の写像のヤコビアンで立体角あたりに直す
+        self.marginal.pdf(y) * self.conditional[y].pdf(x) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+impl Environment for ImageEnvironment {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        // 画素中心を基準にバイリニア補間する
+        let fx = u * self.width as f64 - 0.5;
+        let fy = v * self.height as f64 - 0.5;
+        let (x, y) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - x, fy - y);
+        let (x, y) = (x as isize, y as isize);
+        let top = self.texel(x, y).lerp(self.texel(x + 1, y), tx);
+        let bottom = self.texel(x, y + 1).lerp(self.texel(x + 1, y + 1), tx);
+        top.lerp(bottom, ty) * self.intensity
+    }
+
+    fn is_sampled(&self) -> bool {
+        self.marginal.integral > 0.0
+    }
+
+    fn sample(&self, (u0, u1): (f64, f64)) -> Option<LightSample> {
+        let (v, _, y) = self.marginal.sample(u0);
+        let (u, _, x) = self.conditional[y].sample(u1);
+        let pdf = self.pixel_pdf(x, y, v);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let direction = self.uv_to_direction(u, v);
+        Some(LightSample {
+            direction,
+            distance: f64::MAX,
+            radiance: self.radiance(direction),
+            pdf,
+        })
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let (x, y) = self.pixel_index(u, v);
+        self.pixel_pdf(x, y, v)
+    }
+}
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat, ImageResult, Rgb, Rgb32FImage, RgbImage};
+
+use super::{tonemap::ToneMapper, Color};
+
+/// Floating point framebuffer holding linear radiance
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::zero(); (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    /// Convert to an 8-bit sRGB image through the tone mapping pipeline
+    pub fn to_rgb_image(&self, tone_mapper: &ToneMapper) -> RgbImage {
+        tone_mapper.apply(self)
+    }
+
+    /// Convert to a 32-bit float image keeping the linear HDR values
+    pub fn to_rgb32f_image(&self) -> Rgb32FImage {
+        Rgb32FImage::from_fn(self.width, self.height, |x, y| {
+            let c = self.get(x, y);
+            Rgb([c.x() as f32, c.y() as f32, c.z() as f32])
+        })
+    }
+
+    /// Save in the given format; OpenEXR and Radiance HDR keep the linear float values,
+    /// other formats are tone mapped to 8-bit sRGB
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        format: ImageFormat,
+        tone_mapper: &ToneMapper,
+    ) -> ImageResult<()> {
+        match format {
+            ImageFormat::OpenExr | ImageFormat::Hdr => {
+                DynamicImage::ImageRgb32F(self.to_rgb32f_image()).save_with_format(path, format)
+            }
+            _ => self
+                .to_rgb_image(tone_mapper)
+                .save_with_format(path, format),
+        }
+    }
+}
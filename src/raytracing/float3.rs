@@ -1,4 +1,4 @@
-use super::EPS;
+use super::{rng::random, EPS};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Float3([f64; 3]);
@@ -9,7 +9,7 @@ pub type Point3 = Float3;
 
 impl Float3 {
     pub const fn new(x: f64, y: f64, z: f64) -> Self {
-        Self([x, z, y])
+        Self([x, y, z])
     }
 
     pub const fn zero() -> Self {
@@ -33,10 +33,10 @@ impl Float3 {
     }
 
     pub fn saturate(&self) -> Self {
-        Self::from_iter(self.0.iter().map(|x| x.min(1.0).max(0.0)))
+        Self::from_iter(self.0.iter().map(|x| x.clamp(0.0, 1.0)))
     }
 
-    pub fn to_array(&self) -> [f64; 3] {
+    pub fn to_array(self) -> [f64; 3] {
         self.0
     }
 
@@ -47,6 +47,57 @@ impl Float3 {
     pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, f64> {
         self.0.iter_mut()
     }
+
+    /// Returns the largest component
+    pub fn max_element(&self) -> f64 {
+        self.0.iter().fold(f64::MIN, |acc, x| acc.max(*x))
+    }
+
+    /// Returns the component-wise minimum of two vectors
+    pub fn min(&self, rhs: Self) -> Self {
+        Self([
+            self.0[0].min(rhs.0[0]),
+            self.0[1].min(rhs.0[1]),
+            self.0[2].min(rhs.0[2]),
+        ])
+    }
+
+    /// Returns the component-wise maximum of two vectors
+    pub fn max(&self, rhs: Self) -> Self {
+        Self([
+            self.0[0].max(rhs.0[0]),
+            self.0[1].max(rhs.0[1]),
+            self.0[2].max(rhs.0[2]),
+        ])
+    }
+}
+
+/// implements random utilities
+impl Float3 {
+    /// Construct with random components. [0..1)
+    pub fn random() -> Self {
+        Self::new(random::<f64>(), random::<f64>(), random::<f64>())
+    }
+
+    /// Construct with random components. [min..max)
+    pub fn random_limit(min: f64, max: f64) -> Self {
+        Self::from_iter(Self::random().0.iter().map(|x| min + x * (max - min)))
+    }
+
+    /// Returns a random point inside the unit sphere
+    pub fn random_in_unit_sphere() -> Self {
+        loop {
+            let point = Self::random_limit(-1.0, 1.0);
+            if point.length_squared() < 1.0 {
+                return point;
+            }
+        }
+    }
+
+    /// Returns a random unit vector
+    pub fn random_unit_vector() -> Self {
+        Self::random_in_unit_sphere().normalize()
+    }
 }
 
 impl Float3 {
@@ -93,7 +144,7 @@ impl Float3 {
         let dt = uv.dot(normal);
         let d = 1.0 - ni_over_nt.powi(2) * (1.0 - dt.powi(2));
         if d > 0.0 {
-            Some(-ni_over_nt * (uv - normal * dt) - normal * d.sqrt())
+            Some(ni_over_nt * (uv - normal * dt) - normal * d.sqrt())
         } else {
             None
         }
@@ -104,6 +155,19 @@ impl Float3 {
         *self + (v - *self) * t
     }
 
+    /// Returns two unit vectors that form an orthonormal basis with this unit vector
+    pub fn orthonormal_basis(&self) -> (Self, Self) {
+        // Duff et al. "Building an Orthonormal Basis, Revisited" の分岐の少ない方法
+        let [x, y, z] = self.0;
+        let sign = 1f64.copysign(z);
+        let a = -1.0 / (sign + z);
+        let b = x * y * a;
+        (
+            Self::new(1.0 + sign * x * x * a, sign * b, -sign * x),
+            Self::new(b, sign + y * y * a, -y),
+        )
+    }
+
     pub fn x(&self) -> f64 {
         self.0[0]
     }
@@ -148,18 +212,18 @@ impl Float3 {
     }
 
     /// Returns the array that is represented by r,g,b components
-    pub fn to_rgb(&self) -> [u8; 3] {
+    pub fn to_rgb(self) -> [u8; 3] {
         [self.r(), self.g(), self.b()]
     }
 
     pub fn r(&self) -> u8 {
-        (255.99 * self.0[0].min(1.0).max(0.0)) as u8
+        (255.99 * self.0[0].clamp(0.0, 1.0)) as u8
     }
     pub fn g(&self) -> u8 {
-        (255.99 * self.0[1].min(1.0).max(0.0)) as u8
+        (255.99 * self.0[1].clamp(0.0, 1.0)) as u8
     }
     pub fn b(&self) -> u8 {
-        (255.99 * self.0[2].min(1.0).max(0.0)) as u8
+        (255.99 * self.0[2].clamp(0.0, 1.0)) as u8
     }
 
     /// Convert linear space to gamma space
@@ -172,6 +236,33 @@ impl Float3 {
     pub fn degamma(&self, factor: f64) -> Self {
         Self::from_iter(self.0.iter().map(|x| x.powf(factor)))
     }
+
+    /// Convert linear space to sRGB with the piecewise sRGB transfer function (OETF)
+    pub fn to_srgb(&self) -> Self {
+        Self::from_iter(self.0.iter().map(|&x| {
+            if x <= 0.0031308 {
+                12.92 * x
+            } else {
+                1.055 * x.powf(1.0 / 2.4) - 0.055
+            }
+        }))
+    }
+
+    /// Convert sRGB to linear space, the inverse of `to_srgb`
+    pub fn from_srgb(&self) -> Self {
+        Self::from_iter(self.0.iter().map(|&x| {
+            if x <= 0.04045 {
+                x / 12.92
+            } else {
+                ((x + 0.055) / 1.055).powf(2.4)
+            }
+        }))
+    }
+
+    /// Returns the relative luminance of a linear Rec.709 color
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.0[0] + 0.7152 * self.0[1] + 0.0722 * self.0[2]
+    }
 }
 
 impl FromIterator<f64> for Float3 {
@@ -209,6 +300,47 @@ impl std::ops::Sub<Float3> for Float3 {
     }
 }
 
+/// Index: Float3[axis]
+impl std::ops::Index<usize> for Float3 {
+    type Output = f64;
+    fn index(&self, index: usize) -> &f64 {
+        &self.0[index]
+    }
+}
+
+/// IndexMut: Float3[axis]
+impl std::ops::IndexMut<usize> for Float3 {
+    fn index_mut(&mut self, index: usize) -> &mut f64 {
+        &mut self.0[index]
+    }
+}
+
+/// AddAssign: Float3 += Float3
+impl std::ops::AddAssign<Float3> for Float3 {
+    fn add_assign(&mut self, rhs: Float3) {
+        *self = *self + rhs;
+    }
+}
+
+/// Mul: Float3 * Float3
+impl std::ops::Mul<Float3> for Float3 {
+    type Output = Float3;
+    fn mul(self, rhs: Float3) -> Float3 {
+        Float3([
+            self.0[0] * rhs.0[0],
+            self.0[1] * rhs.0[1],
+            self.0[2] * rhs.0[2],
+        ])
+    }
+}
+
+/// MulAssign: Float3 *= Float3
+impl std::ops::MulAssign<Float3> for Float3 {
+    fn mul_assign(&mut self, rhs: Float3) {
+        *self = *self * rhs;
+    }
+}
+
 /// Mul Scalar: Float3 * f64
 impl std::ops::Mul<f64> for Float3 {
     type Output = Float3;
@@ -1,17 +1,92 @@
-use super::{Point3, Vec3};
+use std::sync::Arc;
+
+use super::{material::Material, medium::MediumInterface, ray::Ray, Point3, Vec3};
 
 pub struct HitInfo {
     pub length: f64,
     pub position: Point3,
+    /// Unit normal pointing out of the shape, whichever side the ray hit
     pub direction: Vec3,
+    /// True if the ray hit the outside, the side `direction` points to
+    pub front_face: bool,
+    pub material: Arc<dyn Material>,
+    /// Texture coordinates
+    pub u: f64,
+    pub v: f64,
+    /// Partial derivatives of the position with respect to u and v
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// Barycentric coordinates of the hit point, for triangles
+    pub barycentric: Option<[f64; 3]>,
+    /// Index of the hit object in the list or BVH holding the scene
+    pub shape_id: Option<usize>,
+    /// Index of the triangle within its mesh, 0 for other shapes
+    pub primitive_id: usize,
+    /// Index of the material in the scene, None for materials the scene does not name
+    pub material_id: Option<usize>,
+    /// Media on the two sides of the surface, None if it does not bound a medium
+    pub medium_interface: Option<MediumInterface>,
+    /// Index in `World::lights` of the light whose geometry was hit
+    pub light_id: Option<usize>,
 }
 
 impl HitInfo {
-    pub const fn new(length: f64, position: Point3, direction: Vec3) -> Self {
+    /// Hit at distance `length` along `ray`, `direction` is the outward unit normal
+    pub fn new(ray: &Ray, length: f64, direction: Vec3, material: Arc<dyn Material>) -> Self {
+        let (dpdu, dpdv) = direction.orthonormal_basis();
         Self {
             length,
-            position,
+            position: ray.at(length),
             direction,
+            front_face: ray.direction.dot(direction) < 0.0,
+            material,
+            u: 0.0,
+            v: 0.0,
+            dpdu,
+            dpdv,
+            barycentric: None,
+            shape_id: None,
+            primitive_id: 0,
+            material_id: None,
+            medium_interface: None,
+            light_id: None,
+        }
+    }
+
+    pub const fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.u = u;
+        self.v = v;
+        self
+    }
+
+    pub const fn with_tangents(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    pub const fn with_barycentric(mut self, barycentric: [f64; 3]) -> Self {
+        self.barycentric = Some(barycentric);
+        self
+    }
+
+    pub const fn with_primitive_id(mut self, primitive_id: usize) -> Self {
+        self.primitive_id = primitive_id;
+        self
+    }
+
+    /// Overrides which side was hit, for shapes whose normal is not the geometric one
+    pub const fn with_front_face(mut self, front_face: bool) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    /// Unit normal on the side the ray came from
+    pub fn facing_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.direction
+        } else {
+            -self.direction
         }
     }
 }
@@ -0,0 +1,508 @@
+use std::sync::Arc;
+
+use super::{
+    aabb::Aabb,
+    csg::Span,
+    hit_info::HitInfo,
+    material::{DiffuseLight, Material},
+    mesh::{Mesh, TriangleMesh},
+    ray::Ray,
+    shapes::{Shape, Sphere},
+    Color, Point3, Vec3, PI, PI2,
+};
+
+/// Light arriving at a point from a position sampled on a light
+pub struct LightSample {
+    /// Unit vector from the receiving point towards the light
+    pub direction: Vec3,
+    /// Distance to the sampled position, `f64::MAX` for directional lights
+    pub distance: f64,
+    /// Incident radiance, or the incident irradiance for delta lights
+    pub radiance: Color,
+    /// Probability density of the direction in solid angle, 1 for delta lights
+    pub pdf: f64,
+}
+
+pub trait Light: Sync + Send {
+    /// Sample a direction towards the light as seen from `position`,
+    /// `u` is a uniform 2D sample. None if the light can't reach `position`
+    fn sample(&self, position: Point3, u: (f64, f64)) -> Option<LightSample>;
+
+    /// Density in solid angle with which `sample` picks `direction` from `position`,
+    /// zero when the direction misses the light
+    fn pdf(&self, _position: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// True for lights without area, which rays can only reach by sampling them
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    /// Geometry of the light, to be added to the scene so rays can hit it. `light_id` is the
+    /// index of the light in `World::lights`, so the integrator knows it samples what was hit.
+    /// None for lights rays can't hit
+    fn shape(&self, _light_id: usize) -> Option<Box<dyn Shape>> {
+        None
+    }
+}
+
+/// Infinitely small light emitting the same intensity in every direction
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, position: Point3, _u: (f64, f64)) -> Option<LightSample> {
+        let d = self.position - position;
+        let distance_squared = d.length_squared();
+        let distance = distance_squared.sqrt();
+        Some(LightSample {
+            direction: d / distance,
+            distance,
+            radiance: self.intensity / distance_squared,
+            pdf: 1.0,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+/// Point light restricted to a cone, with a soft edge
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3,
+    intensity: Color,
+    cos_outer: f64,
+    cos_inner: f64,
+}
+
+impl SpotLight {
+    /// `angle` is the half angle of the cone in degrees, `blend` [0..1] is the fraction of it
+    /// used to fade out towards the edge
+    pub fn new(
+        position: Point3,
+        direction: Vec3,
+        intensity: Color,
+        angle: f64,
+        blend: f64,
+    ) -> Self {
+        let outer = angle.clamp(0.0, 180.0).to_radians();
+        let inner = outer * (1.0 - blend.clamp(0.0, 1.0));
+        Self {
+            position,
+            direction: direction.normalize(),
+            intensity,
+            cos_outer: outer.cos(),
+            cos_inner: inner.cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        // 内側の円錐から外側の円錐に向けてなめらかに暗くする
+        let t = ((cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, position: Point3, _u: (f64, f64)) -> Option<LightSample> {
+        let d = self.position - position;
+        let distance_squared = d.length_squared();
+        let distance = distance_squared.sqrt();
+        let direction = d / distance;
+        let falloff = self.falloff(-direction.dot(self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * (falloff / distance_squared),
+            pdf: 1.0,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+/// Parallel light from an infinitely distant source such as the sun
+pub struct DirectionalLight {
+    /// Direction the light travels in
+    direction: Vec3,
+    irradiance: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        Self {
+            direction: direction.normalize(),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _position: Point3, _u: (f64, f64)) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: f64::MAX,
+            radiance: self.irradiance,
+            pdf: 1.0,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+/// Radiance leaving `position` on an emitter towards `origin`
+fn emitted_towards(
+    material: &Arc<dyn Material>,
+    origin: Point3,
+    position: Point3,
+    normal: Vec3,
+) -> Color {
+    let ray = Ray::new(origin, position - origin);
+    let hit = HitInfo::new(&ray, 1.0, normal, Arc::clone(material));
+    material.emitted(&ray, &hit)
+}
+
+/// Geometry of an area light, hits on it report the index of the light as their `light_id`
+struct LightShape<S> {
+    shape: S,
+    light_id: usize,
+}
+
+impl<S: Shape> Shape for LightShape<S> {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let mut hit = self.shape.hit(ray, t0, t1)?;
+        hit.light_id = Some(self.light_id);
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.shape.bounding_box()
+    }
+
+    fn intervals(&self, ray: &Ray, t0: f64, t1: f64) -> Vec<Span> {
+        let mut spans = self.shape.intervals(ray, t0, t1);
+        for span in &mut spans {
+            for hit in [&mut span.enter.hit, &mut span.exit.hit]
+                .into_iter()
+                .flatten()
+            {
+                hit.light_id = Some(self.light_id);
+            }
+        }
+        spans
+    }
+}
+
+/// Emitting sphere, sampled over the cone of directions it subtends
+pub struct SphereLight {
+    center: Point3,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl SphereLight {
+    pub fn new(center: Point3, radius: f64, emit: Color) -> Self {
+        Self::with_material(center, radius, Arc::new(DiffuseLight::new(emit)))
+    }
+
+    pub fn with_material(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            radius: radius.abs(),
+            material,
+        }
+    }
+
+    /// Cosine of the half angle of the cone the sphere subtends, None from inside
+    fn cos_theta_max(&self, position: Point3) -> Option<f64> {
+        let distance_squared = (self.center - position).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).max(0.0).sqrt())
+    }
+
+    /// Distance along the unit `direction` to the sphere, the far side when starting inside
+    fn intersect(&self, position: Point3, direction: Vec3) -> Option<f64> {
+        let oc = position - self.center;
+        let b = oc.dot(direction);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let d = b * b - c;
+        if d < 0.0 {
+            return None;
+        }
+        let root = d.sqrt();
+        [-b - root, -b + root].into_iter().find(|&t| t > 0.0)
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, position: Point3, (u0, u1): (f64, f64)) -> Option<LightSample> {
+        let phi = PI2 * u1;
+        let (direction, pdf) = match self.cos_theta_max(position) {
+            Some(cos_theta_max) => {
+                // 球が見える円錐の中で一様に方向を選ぶ
+                let axis = (self.center - position).normalize();
+                let (t, b) = axis.orthonormal_basis();
+                let cos_theta = 1.0 - u0 * (1.0 - cos_theta_max);
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let direction = axis * cos_theta + (t * phi.cos() + b * phi.sin()) * sin_theta;
+                (direction, 1.0 / (PI2 * (1.0 - cos_theta_max)))
+            }
+            None => {
+                // 内側からはどの方向にも球があるので全方向から一様に選ぶ
+                let z = 1.0 - 2.0 * u0;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                (Vec3::new(r * phi.cos(), r * phi.sin(), z), 1.0 / (4.0 * PI))
+            }
+        };
+
+        // 円錐の縁ではわずかに外れることがあるので最も近い点で代用する
+        let distance = self
+            .intersect(position, direction)
+            .unwrap_or_else(|| (self.center - position).dot(direction));
+        let point = position + direction * distance;
+        let normal = (point - self.center) / self.radius;
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: emitted_towards(&self.material, position, point, normal),
+            pdf,
+        })
+    }
+
+    fn pdf(&self, position: Point3, direction: Vec3) -> f64 {
+        let direction = direction.normalize();
+        if self.intersect(position, direction).is_none() {
+            return 0.0;
+        }
+        match self.cos_theta_max(position) {
+            Some(cos_theta_max) => 1.0 / (PI2 * (1.0 - cos_theta_max)),
+            None => 1.0 / (4.0 * PI),
+        }
+    }
+
+    fn shape(&self, light_id: usize) -> Option<Box<dyn Shape>> {
+        Some(Box::new(LightShape {
+            shape: Sphere::new(self.center, self.radius, Arc::clone(&self.material)),
+            light_id,
+        }))
+    }
+}
+
+/// Emitting parallelogram spanned by two edges from a corner, sampled uniformly by area
+pub struct RectLight {
+    corner: Point3,
+    edge_u: Vec3,
+    edge_v: Vec3,
+    /// Unnormalized normal, `edge_u x edge_v`
+    normal: Vec3,
+    area: f64,
+    material: Arc<dyn Material>,
+}
+
+impl RectLight {
+    pub fn new(corner: Point3, edge_u: Vec3, edge_v: Vec3, emit: Color) -> Self {
+        Self::with_material(corner, edge_u, edge_v, Arc::new(DiffuseLight::new(emit)))
+    }
+
+    pub fn with_material(
+        corner: Point3,
+        edge_u: Vec3,
+        edge_v: Vec3,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let normal = edge_u.cross(edge_v);
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            normal,
+            area: normal.length(),
+            material,
+        }
+    }
+
+    /// Converts the area density to solid angle as seen from `position`
+    fn solid_angle_pdf(&self, position: Point3, point: Point3) -> f64 {
+        let d = point - position;
+        let distance_squared = d.length_squared();
+        let cosine = (d.dot(self.normal) / (distance_squared.sqrt() * self.area)).abs();
+        if cosine <= 0.0 {
+            0.0
+        } else {
+            distance_squared / (cosine * self.area)
+        }
+    }
+}
+
+impl Light for RectLight {
+    fn sample(&self, position: Point3, (u0, u1): (f64, f64)) -> Option<LightSample> {
+        let point = self.corner + self.edge_u * u0 + self.edge_v * u1;
+        let pdf = self.solid_angle_pdf(position, point);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let d = point - position;
+        let distance = d.length();
+        Some(LightSample {
+            direction: d / distance,
+            distance,
+            radiance: emitted_towards(&self.material, position, point, self.normal / self.area),
+            pdf,
+        })
+    }
+
+    fn pdf(&self, position: Point3, direction: Vec3) -> f64 {
+        let denom = direction.dot(self.normal);
+        if denom == 0.0 {
+            return 0.0;
+        }
+        let t = (self.corner - position).dot(self.normal) / denom;
+        if t <= 0.0 {
+            return 0.0;
+        }
+        // 平面との交点を2辺の座標で表して、範囲内にあるか調べる
+        let point = position + direction * t;
+        let q = point - self.corner;
+        let n2 = self.area * self.area;
+        let a = q.cross(self.edge_v).dot(self.normal) / n2;
+        let b = self.edge_u.cross(q).dot(self.normal) / n2;
+        if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+            return 0.0;
+        }
+        self.solid_angle_pdf(position, point)
+    }
+
+    fn shape(&self, light_id: usize) -> Option<Box<dyn Shape>> {
+        let c = self.corner;
+        let positions = vec![
+            c,
+            c + self.edge_u,
+            c + self.edge_u + self.edge_v,
+            c + self.edge_v,
+        ];
+        let mesh = Mesh::new(
+            positions,
+            vec![[0, 1, 2], [0, 2, 3]],
+            Arc::clone(&self.material),
+        );
+        Some(Box::new(LightShape {
+            shape: TriangleMesh::new(mesh),
+            light_id,
+        }))
+    }
+}
+
+/// Emitting triangle mesh, sampled uniformly by area
+pub struct MeshLight {
+    shape: Arc<TriangleMesh>,
+    /// Cumulative triangle areas
+    cdf: Vec<f64>,
+    area: f64,
+}
+
+impl MeshLight {
+    /// Builds a light from a mesh, the mesh's material provides the emission
+    pub fn new(mesh: impl Into<Arc<Mesh>>) -> Self {
+        let mesh = mesh.into();
+        let mut area = 0.0;
+        let cdf = (0..mesh.triangles.len())
+            .map(|i| {
+                let [p0, p1, p2] = mesh.vertices(i);
+                area += 0.5 * (p1 - p0).cross(p2 - p0).length();
+                area
+            })
+            .collect();
+        Self {
+            shape: Arc::new(TriangleMesh::new(mesh)),
+            cdf,
+            area,
+        }
+    }
+}
+
+impl Light for MeshLight {
+    fn sample(&self, position: Point3, (u0, u1): (f64, f64)) -> Option<LightSample> {
+        if self.area <= 0.0 {
+            return None;
+        }
+        // 面積に比例して三角形を選び、u0 の残りを三角形内の位置に使い回す
+        let target = u0 * self.area;
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= target)
+            .min(self.cdf.len() - 1);
+        let start = if index == 0 { 0.0 } else { self.cdf[index - 1] };
+        let u0 = ((target - start) / (self.cdf[index] - start)).clamp(0.0, 1.0);
+
+        let mesh = self.shape.mesh();
+        let [p0, p1, p2] = mesh.vertices(index);
+        let su0 = u0.sqrt();
+        let point = p0 * (1.0 - su0) + p1 * (su0 * (1.0 - u1)) + p2 * (su0 * u1);
+        let normal = (p1 - p0).cross(p2 - p0).normalize();
+
+        let d = point - position;
+        let distance_squared = d.length_squared();
+        let distance = distance_squared.sqrt();
+        let direction = d / distance;
+        let cosine = direction.dot(normal).abs();
+        if cosine <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: emitted_towards(&mesh.material, position, point, normal),
+            pdf: distance_squared / (cosine * self.area),
+        })
+    }
+
+    fn pdf(&self, position: Point3, direction: Vec3) -> f64 {
+        let ray = Ray::new(position, direction.normalize());
+        let Some(hit) = self.shape.hit(&ray, 0.0, f64::MAX) else {
+            return 0.0;
+        };
+        // sample と同じく補間した法線ではなく三角形の面の法線を使う
+        let [p0, p1, p2] = self.shape.mesh().vertices(hit.primitive_id);
+        let normal = (p1 - p0).cross(p2 - p0).normalize();
+        let cosine = ray.direction.dot(normal).abs();
+        if cosine <= 0.0 {
+            0.0
+        } else {
+            hit.length * hit.length / (cosine * self.area)
+        }
+    }
+
+    fn shape(&self, light_id: usize) -> Option<Box<dyn Shape>> {
+        Some(Box::new(LightShape {
+            shape: Arc::clone(&self.shape),
+            light_id,
+        }))
+    }
+}
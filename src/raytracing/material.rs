@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use super::{
+    hit_info::HitInfo,
+    medium::HomogeneousMedium,
+    ray::Ray,
+    rng::random,
+    texture::{SolidColor, Texture},
+    Color, Float3, Vec3, FRAC_1_PI,
+};
+
+pub struct ScatterInfo {
+    pub ray: Ray,
+    pub albedo: Color,
+}
+
+impl ScatterInfo {
+    pub const fn new(ray: Ray, albedo: Color) -> Self {
+        Self { ray, albedo }
+    }
+}
+
+pub trait Material: Sync + Send {
+    /// Returns the scattered ray and its attenuation, or None if the ray is absorbed
+    fn scatter(&self, ray: &Ray, hit: &HitInfo) -> Option<ScatterInfo>;
+
+    /// Returns the light emitted from the hit point
+    fn emitted(&self, _ray: &Ray, _hit: &HitInfo) -> Color {
+        Color::zero()
+    }
+
+    /// True if the material scatters only into discrete directions, which light sampling
+    /// can never pick, so the integrator skips it
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    /// Returns the BSDF times the cosine term for light arriving from the unit vector
+    /// `direction` and leaving towards the origin of `ray`
+    fn eval(&self, _ray: &Ray, _hit: &HitInfo, _direction: Vec3) -> Color {
+        Color::zero()
+    }
+
+    /// Returns the density in solid angle with which `scatter` picks the unit vector
+    /// `direction`, zero for specular materials
+    fn pdf(&self, _ray: &Ray, _hit: &HitInfo, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// True for surfaces that only separate media, which rays pass through unchanged
+    fn is_interface(&self) -> bool {
+        false
+    }
+
+    /// Medium inside a closed shape of this material at the hit point, which paths scattered
+    /// into the shape walk through until they leave it, see `subsurface::Subsurface`
+    fn subsurface(&self, _hit: &HitInfo) -> Option<HomogeneousMedium> {
+        None
+    }
+}
+
+/// Returns the value of `texture` at the hit point
+pub fn texture_value(texture: &dyn Texture, hit: &HitInfo) -> Color {
+    texture.value(hit.u, hit.v, hit.position)
+}
+
+/// Diffuse material
+pub struct Lambertian {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(&self, _ray: &Ray, hit: &HitInfo) -> Option<ScatterInfo> {
+        let normal = hit.facing_normal();
+        let mut direction = normal + Float3::random_unit_vector();
+        // 法線と逆向きの乱数ベクトルが出ると長さ0になるので法線方向に散乱させる
+        if direction.near_zero() {
+            direction = normal;
+        }
+        Some(ScatterInfo::new(
+            Ray::new(hit.position, direction),
+            texture_value(self.albedo.as_ref(), hit),
+        ))
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, _ray: &Ray, hit: &HitInfo, direction: Vec3) -> Color {
+        let cosine = hit.facing_normal().dot(direction);
+        if cosine <= 0.0 {
+            Color::zero()
+        } else {
+            texture_value(self.albedo.as_ref(), hit) * (cosine * FRAC_1_PI)
+        }
+    }
+
+    fn pdf(&self, _ray: &Ray, hit: &HitInfo, direction: Vec3) -> f64 {
+        // 法線と単位球上の点の和はコサインに比例した分布になる
+        (hit.facing_normal().dot(direction) * FRAC_1_PI).max(0.0)
+    }
+}
+
+/// Metal material, `fuzz` blurs the reflection. [0..1]
+pub struct Metal {
+    albedo: Arc<dyn Texture>,
+    fuzz: f64,
+}
+
+impl Metal {
+    pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
+        Self {
+            albedo,
+            fuzz: fuzz.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Material for Metal {
+    fn scatter(&self, ray: &Ray, hit: &HitInfo) -> Option<ScatterInfo> {
+        let normal = hit.facing_normal();
+        let mut reflected = ray.direction.normalize().reflect(normal);
+        reflected += self.fuzz * Float3::random_in_unit_sphere();
+        // 表面の内側に潜り込んだ反射は吸収されたとみなす
+        if reflected.dot(normal) > 0.0 {
+            Some(ScatterInfo::new(
+                Ray::new(hit.position, reflected),
+                texture_value(self.albedo.as_ref(), hit),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Glass-like material, `ri` is the refractive index
+pub struct Dielectric {
+    ri: f64,
+}
+
+impl Dielectric {
+    pub fn new(ri: f64) -> Self {
+        Self { ri }
+    }
+
+    /// Schlick's approximation of the Fresnel reflectance
+    pub fn schlick(cosine: f64, ri: f64) -> f64 {
+        let r0 = ((1.0 - ri) / (1.0 + ri)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray: &Ray, hit: &HitInfo) -> Option<ScatterInfo> {
+        let direction = ray.direction.normalize();
+        let (outward_normal, ni_over_nt, cosine) = if hit.front_face {
+            // 外側から内側へ
+            let cosine = -direction.dot(hit.direction);
+            (hit.direction, self.ri.recip(), cosine)
+        } else {
+            // 内側から外側へ
+            let cosine = self.ri * direction.dot(hit.direction);
+            (-hit.direction, self.ri, cosine)
+        };
+
+        let scattered = match direction.refract(outward_normal, ni_over_nt) {
+            Some(refracted) if random::<f64>() >= Self::schlick(cosine, self.ri) => refracted,
+            _ => direction.reflect(hit.direction),
+        };
+
+        Some(ScatterInfo::new(
+            Ray::new(hit.position, scattered),
+            Color::one(),
+        ))
+    }
+}
+
+/// Emissive material
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &HitInfo) -> Option<ScatterInfo> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, _hit: &HitInfo) -> Color {
+        self.emit
+    }
+}
+
+/// Invisible surface marking the boundary of a medium, see `medium::Volume`
+pub struct InterfaceMaterial;
+
+impl Material for InterfaceMaterial {
+    fn scatter(&self, ray: &Ray, hit: &HitInfo) -> Option<ScatterInfo> {
+        Some(ScatterInfo::new(
+            Ray::new(hit.position, ray.direction),
+            Color::one(),
+        ))
+    }
+
+    fn is_interface(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,436 @@
+//! Participating media such as fog, smoke and murky liquids.
+//!
+//! A medium fills the inside or outside of a shape wrapped in a `Volume`, or the whole scene
+//! when the camera is in it. Rays through a medium are attenuated and may scatter inside it
+//! according to a Henyey-Greenstein phase function.
+
+use std::{fs, io, path::Path, sync::Arc};
+
+use super::{
+    aabb::Aabb, csg::Span, hit_info::HitInfo, microfacet::Onb, ray::Ray, rng::random,
+    shapes::Shape, texture::Perlin, Color, Point3, Vec3, PI, PI2,
+};
+
+/// Henyey-Greenstein phase function, `g` in (-1..1) is the mean cosine of the scattering
+/// angle, positive values scatter forward
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HenyeyGreenstein {
+    pub g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> Self {
+        Self {
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    /// Density of scattering from the unit vector `direction` the light travels in into the
+    /// unit vector `scattered`, per solid angle
+    pub fn p(&self, direction: Vec3, scattered: Vec3) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * direction.dot(scattered);
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+
+    /// Sample a scattered direction for light travelling in the unit vector `direction`,
+    /// returns it with its density. The phase function is sampled exactly
+    pub fn sample(&self, direction: Vec3, (u0, u1): (f64, f64)) -> (Vec3, f64) {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u0
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u0);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        }
+        .clamp(-1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let (sin_phi, cos_phi) = (PI2 * u1).sin_cos();
+        let scattered = Onb::from_normal(direction).to_world(Vec3::new(
+            sin_theta * cos_phi,
+            sin_theta * sin_phi,
+            cos_theta,
+        ));
+        (scattered, self.p(direction, scattered))
+    }
+}
+
+/// Result of following a ray through a medium
+pub struct MediumSample {
+    /// Factor for the path throughput, the transmittance divided by the sampling density
+    /// and, at a scattering event, times the scattering coefficient
+    pub weight: Color,
+    /// Distance along the ray where it scatters, None if it passes through
+    pub scatter: Option<f64>,
+}
+
+pub trait Medium: Sync + Send {
+    /// Sample where a ray travelling from its origin up to `t_max` scatters, if at all
+    fn sample(&self, ray: &Ray, t_max: f64) -> MediumSample;
+
+    /// Fraction of light that passes the ray between its origin and `t_max`, may be a
+    /// random estimate
+    fn transmittance(&self, ray: &Ray, t_max: f64) -> Color;
+
+    fn phase(&self) -> &HenyeyGreenstein;
+}
+
+/// Transmittance `exp(-sigma_t * distance)`, where channels without extinction stay 1
+fn beer_lambert(sigma_t: Color, distance: f64) -> Color {
+    Color::from_iter(
+        sigma_t
+            .iter()
+            .map(|&s| if s == 0.0 { 1.0 } else { (-s * distance).exp() }),
+    )
+}
+
+/// Medium with the same coefficients everywhere
+pub struct HomogeneousMedium {
+    sigma_a: Color,
+    sigma_s: Color,
+    phase: HenyeyGreenstein,
+}
+
+impl HomogeneousMedium {
+    /// Absorption and scattering coefficients per unit of length
+    pub fn new(sigma_a: Color, sigma_s: Color, g: f64) -> Self {
+        Self {
+            sigma_a,
+            sigma_s,
+            phase: HenyeyGreenstein::new(g),
+        }
+    }
+
+    /// Like `sample`, but picks the channel whose coefficient the distance is sampled with in
+    /// proportion to `channel_weights`. Passing the throughput keeps long random walks
+    /// through colored media from getting noisy
+    pub fn sample_weighted(&self, ray: &Ray, t_max: f64, channel_weights: Color) -> MediumSample {
+        let total = channel_weights.iter().sum::<f64>();
+        if total <= 0.0 {
+            return MediumSample {
+                weight: Color::zero(),
+                scatter: None,
+            };
+        }
+        let length = ray.direction.length();
+        let sigma_t = self.sigma_a + self.sigma_s;
+        // 色ごとに減衰が違うので、チャンネルを1つ選んでその係数で距離をサンプリングする
+        let mut u = random::<f64>() * total;
+        let channel = (0..2)
+            .find(|&i| {
+                u -= channel_weights[i];
+                u < 0.0
+            })
+            .unwrap_or(2);
+        let distance = -(1.0 - random::<f64>()).ln() / sigma_t[channel];
+        let scattered = distance < t_max * length;
+        let distance = if scattered { distance } else { t_max * length };
+
+        let tr = beer_lambert(sigma_t, distance);
+        // チャンネルごとの戦略を選ぶ確率で混ぜた確率密度
+        let density = if scattered { sigma_t * tr } else { tr };
+        let pdf = (density * channel_weights).iter().sum::<f64>() / total;
+        if pdf <= 0.0 {
+            return MediumSample {
+                weight: Color::zero(),
+                scatter: None,
+            };
+        }
+        MediumSample {
+            weight: if scattered { tr * self.sigma_s } else { tr } / pdf,
+            scatter: scattered.then_some(distance / length),
+        }
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    fn sample(&self, ray: &Ray, t_max: f64) -> MediumSample {
+        self.sample_weighted(ray, t_max, Color::one())
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f64) -> Color {
+        beer_lambert(self.sigma_a + self.sigma_s, t_max * ray.direction.length())
+    }
+
+    fn phase(&self) -> &HenyeyGreenstein {
+        &self.phase
+    }
+}
+
+/// Densities on a regular grid of voxels, looked up with trilinear interpolation
+pub struct DensityGrid {
+    size: [usize; 3],
+    values: Vec<f64>,
+    max_value: f64,
+}
+
+impl DensityGrid {
+    /// Grid from values ordered with x changing fastest, then y, then z. None if the number of
+    /// values does not match the size
+    pub fn new(size: [usize; 3], values: Vec<f64>) -> Option<Self> {
+        if size.contains(&0) || values.len() != size.iter().product() {
+            return None;
+        }
+        let max_value = values.iter().copied().fold(0.0, f64::max);
+        Some(Self {
+            size,
+            values,
+            max_value,
+        })
+    }
+
+    /// Grid sampling `density` at the center of each voxel, given coordinates in [0..1]
+    pub fn from_fn(size: [usize; 3], density: impl Fn(Point3) -> f64) -> Self {
+        let [nx, ny, nz] = size.map(|n| n.max(1));
+        let values = (0..nx * ny * nz)
+            .map(|i| {
+                let (x, y, z) = (i % nx, i / nx % ny, i / (nx * ny));
+                density(Point3::new(
+                    (x as f64 + 0.5) / nx as f64,
+                    (y as f64 + 0.5) / ny as f64,
+                    (z as f64 + 0.5) / nz as f64,
+                ))
+                .max(0.0)
+            })
+            .collect();
+        Self::new([nx, ny, nz], values).unwrap()
+    }
+
+    /// Cloud of fractal noise fading out towards the edges of the grid
+    pub fn noise(size: [usize; 3], scale: f64, octaves: u32, seed: u64) -> Self {
+        let perlin = Perlin::new(seed);
+        Self::from_fn(size, |p| {
+            let falloff = (1.0 - (p * 2.0 - Vec3::one()).length_squared()).max(0.0);
+            (0.5 + perlin.fbm(p * scale, octaves)) * falloff
+        })
+    }
+
+    /// Load a raw voxel file without a header: one byte per voxel mapped to [0..1], or
+    /// little-endian 32-bit floats, told apart by the length of the file. Negative densities
+    /// are clamped to 0 and non-finite ones rejected
+    pub fn load_raw(path: impl AsRef<Path>, size: [usize; 3]) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let count: usize = size.iter().product();
+        let values = if bytes.len() == count {
+            bytes.iter().map(|&b| b as f64 / 255.0).collect()
+        } else if bytes.len() == count * 4 {
+            let values: Vec<f64> = bytes
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64)
+                .collect();
+            if let Some(i) = values.iter().position(|v| !v.is_finite()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("voxel {} is not a finite number", i),
+                ));
+            }
+            // 負の密度は from_fn と同じく0にそろえる
+            values.into_iter().map(|v| v.max(0.0)).collect()
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} bytes for 8-bit or {} for float voxels, found {}",
+                    count,
+                    count * 4,
+                    bytes.len()
+                ),
+            ));
+        };
+        Self::new(size, values)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty voxel grid"))
+    }
+
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    pub fn max_value(&self) -> f64 {
+        self.max_value
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.size;
+        self.values[x + nx * (y + ny * z)]
+    }
+
+    /// Density at `p` in [0..1] on each axis, the grid is clamped at its edges
+    pub fn lookup(&self, p: Point3) -> f64 {
+        // ボクセルの中心を格子点として補間する
+        let mut base = [0; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let n = self.size[axis];
+            let x = (p[axis] * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            base[axis] = (x as usize).min(n.saturating_sub(2));
+            frac[axis] = x - base[axis] as f64;
+        }
+        let next = |axis: usize| (base[axis] + 1).min(self.size[axis] - 1);
+        let [x0, y0, z0] = base;
+        let (x1, y1, z1) = (next(0), next(1), next(2));
+        let [fx, fy, fz] = frac;
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let y_lerp = |z: usize| {
+            lerp(
+                lerp(self.voxel(x0, y0, z), self.voxel(x1, y0, z), fx),
+                lerp(self.voxel(x0, y1, z), self.voxel(x1, y1, z), fx),
+                fy,
+            )
+        };
+        lerp(y_lerp(z0), y_lerp(z1), fz)
+    }
+}
+
+/// Medium whose density varies over a box, following a `DensityGrid`.
+///
+/// Extinction is the same for every color, `albedo` is the fraction of it that scatters.
+/// Distances are sampled by delta tracking and transmittance is estimated by ratio tracking
+/// against the largest density in the grid.
+pub struct GridMedium {
+    grid: DensityGrid,
+    bounds: Aabb,
+    /// Extinction coefficient where the density is 1
+    extinction: f64,
+    albedo: Color,
+    phase: HenyeyGreenstein,
+}
+
+impl GridMedium {
+    pub fn new(grid: DensityGrid, bounds: Aabb, extinction: f64, albedo: Color, g: f64) -> Self {
+        Self {
+            grid,
+            bounds,
+            extinction,
+            albedo,
+            phase: HenyeyGreenstein::new(g),
+        }
+    }
+
+    fn density(&self, p: Point3) -> f64 {
+        let extent = self.bounds.extent();
+        let local = p - self.bounds.min;
+        self.grid.lookup(Point3::new(
+            local.x() / extent.x(),
+            local.y() / extent.y(),
+            local.z() / extent.z(),
+        ))
+    }
+
+    /// Part of the ray up to `t_max` inside the grid and the majorant per unit of t
+    fn range(&self, ray: &Ray, t_max: f64) -> Option<(f64, f64, f64)> {
+        let majorant = self.grid.max_value() * self.extinction * ray.direction.length();
+        if majorant <= 0.0 {
+            return None;
+        }
+        let (start, end) = self.bounds.clip(ray, 0.0, t_max)?;
+        Some((start, end, majorant))
+    }
+}
+
+impl Medium for GridMedium {
+    fn sample(&self, ray: &Ray, t_max: f64) -> MediumSample {
+        let mut sample = MediumSample {
+            weight: Color::one(),
+            scatter: None,
+        };
+        let Some((mut t, end, majorant)) = self.range(ray, t_max) else {
+            return sample;
+        };
+        // 最大密度の一様な媒質として衝突点を選び、実際の密度の割合で本当の衝突とみなす
+        loop {
+            t -= (1.0 - random::<f64>()).ln() / majorant;
+            if t >= end {
+                return sample;
+            }
+            let density = self.density(ray.at(t)) / self.grid.max_value();
+            if random::<f64>() < density {
+                sample.weight = self.albedo;
+                sample.scatter = Some(t);
+                return sample;
+            }
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f64) -> Color {
+        let Some((mut t, end, majorant)) = self.range(ray, t_max) else {
+            return Color::one();
+        };
+        // 衝突点ごとに素通りする割合を掛けていく
+        let mut tr = 1.0;
+        loop {
+            t -= (1.0 - random::<f64>()).ln() / majorant;
+            if t >= end {
+                return Color::full(tr);
+            }
+            tr *= 1.0 - self.density(ray.at(t)) / self.grid.max_value();
+            if tr <= 0.0 {
+                return Color::zero();
+            }
+        }
+    }
+
+    fn phase(&self) -> &HenyeyGreenstein {
+        &self.phase
+    }
+}
+
+/// Media on the two sides of a surface, None for vacuum
+#[derive(Clone, Default)]
+pub struct MediumInterface {
+    pub inside: Option<Arc<dyn Medium>>,
+    pub outside: Option<Arc<dyn Medium>>,
+}
+
+impl MediumInterface {
+    pub fn new(inside: Option<Arc<dyn Medium>>, outside: Option<Arc<dyn Medium>>) -> Self {
+        Self { inside, outside }
+    }
+
+    /// Medium a ray leaving the surface in `direction` enters, `normal` points outward
+    pub fn towards(&self, direction: Vec3, normal: Vec3) -> Option<Arc<dyn Medium>> {
+        if direction.dot(normal) < 0.0 {
+            self.inside.clone()
+        } else {
+            self.outside.clone()
+        }
+    }
+}
+
+/// Binds media to the boundary of a shape.
+///
+/// The surface keeps its materials, give it `InterfaceMaterial` for a boundary that only
+/// separates the media.
+pub struct Volume {
+    shape: Box<dyn Shape>,
+    interface: MediumInterface,
+}
+
+impl Volume {
+    pub fn new(shape: Box<dyn Shape>, interface: MediumInterface) -> Self {
+        Self { shape, interface }
+    }
+}
+
+impl Shape for Volume {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let mut hit = self.shape.hit(ray, t0, t1)?;
+        hit.medium_interface = Some(self.interface.clone());
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.shape.bounding_box()
+    }
+
+    fn intervals(&self, ray: &Ray, t0: f64, t1: f64) -> Vec<Span> {
+        let mut spans = self.shape.intervals(ray, t0, t1);
+        for span in &mut spans {
+            for boundary in [&mut span.enter, &mut span.exit] {
+                if let Some(hit) = &mut boundary.hit {
+                    hit.medium_interface = Some(self.interface.clone());
+                }
+            }
+        }
+        spans
+    }
+}
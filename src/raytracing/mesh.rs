@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use super::{
+    aabb::Aabb,
+    bvh::Bvh,
+    hit_info::HitInfo,
+    material::Material,
+    ray::Ray,
+    shapes::{intersect_triangle, Shape},
+    Point3, Vec3,
+};
+
+/// Indexed triangle buffers shared by every triangle of a mesh.
+///
+/// `normals` and `uvs` are either empty or hold one entry per position.
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub triangles: Vec<[usize; 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Mesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        triangles: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            triangles,
+            material,
+        }
+    }
+
+    /// Vertex normals, one per position. None if the counts differ
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Option<Self> {
+        if normals.len() != self.positions.len() {
+            return None;
+        }
+        self.normals = normals;
+        Some(self)
+    }
+
+    /// Texture coordinates, one per position. None if the counts differ
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> Option<Self> {
+        if uvs.len() != self.positions.len() {
+            return None;
+        }
+        self.uvs = uvs;
+        Some(self)
+    }
+
+    pub fn vertices(&self, index: usize) -> [Point3; 3] {
+        self.triangles[index].map(|i| self.positions[i])
+    }
+
+    /// Compute the hit information of a triangle from its barycentric coordinates
+    fn hit_info(&self, index: usize, ray: &Ray, t: f64, barycentric: [f64; 3]) -> HitInfo {
+        let [i0, i1, i2] = self.triangles[index];
+        let [b0, b1, b2] = barycentric;
+        let [p0, p1, p2] = self.vertices(index);
+        let geometric_normal = (p1 - p0).cross(p2 - p0).normalize();
+
+        let normal = if self.normals.is_empty() {
+            geometric_normal
+        } else {
+            // 頂点法線を補間してなめらかな陰影にする
+            (b0 * self.normals[i0] + b1 * self.normals[i1] + b2 * self.normals[i2]).normalize()
+        };
+        // 頂点法線が巻き順と逆向きのときは、頂点法線の側を外側とする
+        let geometric_normal = if geometric_normal.dot(normal) < 0.0 {
+            -geometric_normal
+        } else {
+            geometric_normal
+        };
+
+        let ((u, v), (dpdu, dpdv)) = if self.uvs.is_empty() {
+            ((b1, b2), (p1 - p0, p2 - p0))
+        } else {
+            let [(u0, v0), (u1, v1), (u2, v2)] = [self.uvs[i0], self.uvs[i1], self.uvs[i2]];
+            let uv = (b0 * u0 + b1 * u1 + b2 * u2, b0 * v0 + b1 * v1 + b2 * v2);
+            // 辺ベクトルを UV の差で表す連立方程式を解く
+            let (du1, dv1, du2, dv2) = (u1 - u0, v1 - v0, u2 - u0, v2 - v0);
+            let det = du1 * dv2 - dv1 * du2;
+            let tangents = if det.abs() < 1e-12 {
+                geometric_normal.orthonormal_basis()
+            } else {
+                let (e1, e2) = (p1 - p0, p2 - p0);
+                ((dv2 * e1 - dv1 * e2) / det, (du1 * e2 - du2 * e1) / det)
+            };
+            (uv, tangents)
+        };
+
+        // 表裏は補間した法線ではなく面の向きで決める
+        HitInfo::new(ray, t, normal, Arc::clone(&self.material))
+            .with_front_face(ray.direction.dot(geometric_normal) < 0.0)
+            .with_uv(u, v)
+            .with_tangents(dpdu, dpdv)
+            .with_barycentric(barycentric)
+            .with_primitive_id(index)
+    }
+}
+
+/// A triangle referencing the shared buffers of its mesh
+struct MeshTriangle {
+    mesh: Arc<Mesh>,
+    index: usize,
+}
+
+impl Shape for MeshTriangle {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let (t, barycentric) = intersect_triangle(ray, self.mesh.vertices(self.index), t0, t1)?;
+        Some(self.mesh.hit_info(self.index, ray, t, barycentric))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.mesh.vertices(self.index)))
+    }
+}
+
+/// Triangle mesh shape, the triangles are accelerated by their own BVH
+pub struct TriangleMesh {
+    mesh: Arc<Mesh>,
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: impl Into<Arc<Mesh>>) -> Self {
+        let mesh = mesh.into();
+        let triangles = (0..mesh.triangles.len())
+            .map(|index| {
+                Box::new(MeshTriangle {
+                    mesh: Arc::clone(&mesh),
+                    index,
+                }) as Box<dyn Shape>
+            })
+            .collect();
+        Self {
+            mesh,
+            bvh: Bvh::new(triangles),
+        }
+    }
+
+    pub fn mesh(&self) -> &Arc<Mesh> {
+        &self.mesh
+    }
+}
+
+impl Shape for TriangleMesh {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        self.bvh.hit(ray, t0, t1)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracing::{material::Lambertian, Color};
+
+    #[test]
+    fn vertex_normals_against_the_winding_decide_the_outside() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        // 巻き順では +z 向きだが、頂点法線は -z を向いている
+        let mesh = Mesh::new(
+            vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            vec![[0, 1, 2]],
+            material,
+        )
+        .with_normals(vec![Vec3::new(0.0, 0.0, -1.0); 3])
+        .unwrap();
+        let mesh = TriangleMesh::new(mesh);
+
+        let ray = Ray::new(Point3::new(0.2, 0.2, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = mesh.hit(&ray, 1e-6, f64::INFINITY).unwrap();
+        assert!(hit.front_face);
+        assert_eq!(hit.facing_normal(), Vec3::new(0.0, 0.0, -1.0));
+
+        let ray = Ray::new(Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = mesh.hit(&ray, 1e-6, f64::INFINITY).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.facing_normal(), Vec3::new(0.0, 0.0, 1.0));
+    }
+}
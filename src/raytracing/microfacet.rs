@@ -0,0 +1,215 @@
+//! GGX (Trowbridge-Reitz) microfacet distribution and Fresnel terms.
+//!
+//! Directions are given in a local shading frame where the normal is +z.
+
+use super::{Color, Vec3, PI, PI2};
+
+/// Orthonormal shading frame, `n` is the normal and `t` the tangent
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub t: Vec3,
+    pub b: Vec3,
+    pub n: Vec3,
+}
+
+impl Onb {
+    /// Frame around a unit normal with an arbitrary tangent
+    pub fn from_normal(n: Vec3) -> Self {
+        let (t, b) = n.orthonormal_basis();
+        Self { t, b, n }
+    }
+
+    /// Frame around a unit normal with the tangent as close to `tangent` as possible
+    pub fn from_normal_tangent(n: Vec3, tangent: Vec3) -> Self {
+        // 法線に垂直な成分だけを残す
+        let t = tangent - n * n.dot(tangent);
+        if t.near_zero() {
+            return Self::from_normal(n);
+        }
+        let t = t.normalize();
+        Self {
+            t,
+            b: n.cross(t),
+            n,
+        }
+    }
+
+    /// Rotate the tangent around the normal by `angle` radians
+    pub fn rotate(&self, angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self {
+            t: self.t * c + self.b * s,
+            b: self.b * c - self.t * s,
+            n: self.n,
+        }
+    }
+
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.t), v.dot(self.b), v.dot(self.n))
+    }
+
+    pub fn to_world(&self, v: Vec3) -> Vec3 {
+        self.t * v.x() + self.b * v.y() + self.n * v.z()
+    }
+}
+
+/// Anisotropic GGX distribution of microfacet normals
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Ggx {
+    /// Smallest roughness, smoother surfaces are numerically a mirror
+    const MIN_ALPHA: f64 = 1e-3;
+
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self {
+            alpha_x: alpha_x.max(Self::MIN_ALPHA),
+            alpha_y: alpha_y.max(Self::MIN_ALPHA),
+        }
+    }
+
+    /// Map perceptual roughness and anisotropy [0..1] to alphas the way the Disney BRDF does
+    pub fn from_roughness(roughness: f64, anisotropic: f64) -> Self {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropic.clamp(0.0, 1.0)).sqrt();
+        Self::new(alpha / aspect, alpha * aspect)
+    }
+
+    /// Density of microfacet normal `m`
+    pub fn d(&self, m: Vec3) -> f64 {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = m.x() / self.alpha_x;
+        let y = m.y() / self.alpha_y;
+        let e = x * x + y * y + m.z() * m.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Smith's auxiliary function
+    fn lambda(&self, w: Vec3) -> f64 {
+        let z2 = w.z() * w.z();
+        if z2 <= 0.0 {
+            return 0.0;
+        }
+        let ax = self.alpha_x * w.x();
+        let ay = self.alpha_y * w.y();
+        ((1.0 + (ax * ax + ay * ay) / z2).sqrt() - 1.0) * 0.5
+    }
+
+    /// Masking of direction `w`
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height-correlated masking-shadowing of `wo` and `wi`
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Sample a microfacet normal visible from `wo` (Heitz 2018)
+    pub fn sample_visible_normal(&self, wo: Vec3, (u0, u1): (f64, f64)) -> Vec3 {
+        // 楕円体を半球に引き伸ばした空間で考える
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).normalize();
+        let len_sq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len_sq > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len_sq.sqrt()
+        } else {
+            Vec3::xaxis()
+        };
+        let t2 = vh.cross(t1);
+
+        // 見えている部分の射影に一様に点を取る
+        let r = u0.sqrt();
+        let phi = PI2 * u1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+        Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        )
+        .normalize()
+    }
+
+    /// Density of the reflected direction when the normal is sampled with `sample_visible_normal`
+    pub fn reflection_pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let m = (wo + wi).normalize();
+        if wo.z() <= 0.0 || m.near_zero() {
+            return 0.0;
+        }
+        // 可視法線の密度 G1 D (wo・m) / wo.z に反射のヤコビアン 1 / (4 wo・m) を掛ける
+        self.g1(wo) * self.d(m) / (4.0 * wo.z())
+    }
+}
+
+/// Cosine weighted direction on the +z hemisphere
+pub fn cosine_hemisphere((u0, u1): (f64, f64)) -> Vec3 {
+    let r = u0.sqrt();
+    let phi = PI2 * u1;
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - u0).max(0.0).sqrt())
+}
+
+/// Schlick's approximation with a colored reflectance at normal incidence
+pub fn fresnel_schlick(f0: Color, cos_theta: f64) -> Color {
+    let m = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+    f0 + (Color::one() - f0) * m
+}
+
+/// Exact Fresnel reflectance of a conductor with complex index of refraction `eta + i k`
+pub fn fresnel_conductor(cos_theta: f64, eta: Color, k: Color) -> Color {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    Color::from_iter((0..3).map(|i| {
+        let (eta2, k2) = (eta[i] * eta[i], k[i] * k[i]);
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos2.sqrt() * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    }))
+}
+
+/// Complex index of refraction of common metals at red, green and blue wavelengths
+pub mod conductors {
+    use super::Color;
+
+    pub const GOLD: (Color, Color) = (
+        Color::new(0.143, 0.374, 1.442),
+        Color::new(3.983, 2.386, 1.603),
+    );
+    pub const SILVER: (Color, Color) = (
+        Color::new(0.155, 0.117, 0.138),
+        Color::new(4.828, 3.122, 2.147),
+    );
+    pub const COPPER: (Color, Color) = (
+        Color::new(0.200, 0.924, 1.102),
+        Color::new(3.912, 2.452, 2.142),
+    );
+    pub const ALUMINIUM: (Color, Color) = (
+        Color::new(1.657, 0.880, 0.521),
+        Color::new(9.224, 6.270, 4.837),
+    );
+
+    /// Look up a metal by name
+    pub fn by_name(name: &str) -> Option<(Color, Color)> {
+        match name.to_ascii_lowercase().as_str() {
+            "gold" | "au" => Some(GOLD),
+            "silver" | "ag" => Some(SILVER),
+            "copper" | "cu" => Some(COPPER),
+            "aluminium" | "aluminum" | "al" => Some(ALUMINIUM),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,763 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::{
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    mesh::{Mesh, TriangleMesh},
+    principled::Principled,
+    shapes::Shape,
+    texture::{ImageTexture, Texture},
+    Color, Float3, Point3, Vec3,
+};
+
+#[derive(Debug)]
+pub enum ObjErrorKind {
+    Io(std::io::Error),
+    /// A malformed statement
+    Syntax(String),
+    /// A face references a vertex, texture coordinate or normal that does not exist
+    IndexOutOfRange(i64),
+    /// `usemtl` names a material no loaded MTL file defines
+    UnknownMaterial(String),
+    /// A texture referenced by an MTL file failed to load
+    Texture(image::ImageError),
+}
+
+/// Error raised while loading an OBJ or MTL file
+#[derive(Debug)]
+pub struct ObjError {
+    pub file: PathBuf,
+    /// 1-based line number, None when the error concerns the whole file
+    pub line: Option<usize>,
+    pub kind: ObjErrorKind,
+}
+
+impl ObjError {
+    fn new(file: &Path, line: usize, kind: ObjErrorKind) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            line: Some(line),
+            kind,
+        }
+    }
+
+    fn syntax(file: &Path, line: usize, message: impl Into<String>) -> Self {
+        Self::new(file, line, ObjErrorKind::Syntax(message.into()))
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        match &self.kind {
+            ObjErrorKind::Io(e) => write!(f, ": {}", e),
+            ObjErrorKind::Syntax(message) => write!(f, ": {}", message),
+            ObjErrorKind::IndexOutOfRange(index) => write!(f, ": index {} out of range", index),
+            ObjErrorKind::UnknownMaterial(name) => write!(f, ": unknown material `{}`", name),
+            ObjErrorKind::Texture(e) => write!(f, ": {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ObjErrorKind::Io(e) => Some(e),
+            ObjErrorKind::Texture(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Material description read from an MTL file. Bump maps and other statements the renderer
+/// has no use for are ignored
+#[derive(Debug, Clone)]
+pub struct ObjMaterial {
+    pub name: String,
+    /// Kd
+    pub diffuse: Color,
+    /// Ks
+    pub specular: Color,
+    /// Ke
+    pub emission: Color,
+    /// Ns
+    pub shininess: f64,
+    /// Ni
+    pub ior: f64,
+    /// d, or 1 - Tr
+    pub dissolve: f64,
+    pub illum: u32,
+    /// Pr, from the PBR extension
+    pub roughness: Option<f64>,
+    /// Pm, from the PBR extension
+    pub metallic: Option<f64>,
+    /// Ps, from the PBR extension
+    pub sheen: f64,
+    /// Pc, from the PBR extension
+    pub clearcoat: f64,
+    /// Pcr, from the PBR extension
+    pub clearcoat_roughness: f64,
+    /// aniso, from the PBR extension
+    pub anisotropy: f64,
+    /// anisor, from the PBR extension
+    pub anisotropy_rotation: f64,
+    /// map_Kd, resolved relative to the MTL file
+    pub diffuse_map: Option<PathBuf>,
+    /// map_Ks, resolved relative to the MTL file
+    pub specular_map: Option<PathBuf>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            diffuse: Color::full(0.8),
+            specular: Color::zero(),
+            emission: Color::zero(),
+            shininess: 0.0,
+            ior: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+            roughness: None,
+            metallic: None,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            anisotropy: 0.0,
+            anisotropy_rotation: 0.0,
+            diffuse_map: None,
+            specular_map: None,
+        }
+    }
+
+    /// Convert to the closest material the renderer supports, loading its diffuse and
+    /// specular maps
+    pub fn to_material(&self) -> Result<Arc<dyn Material>, ObjError> {
+        let load = |map: &Option<PathBuf>| -> Result<Option<Arc<dyn Texture>>, ObjError> {
+            match map {
+                Some(path) => Ok(Some(Arc::new(ImageTexture::load(path, 2.2).map_err(
+                    |e| ObjError {
+                        file: path.clone(),
+                        line: None,
+                        kind: ObjErrorKind::Texture(e),
+                    },
+                )?))),
+                None => Ok(None),
+            }
+        };
+        let diffuse_map = load(&self.diffuse_map)?;
+
+        Ok(if !self.emission.near_zero() {
+            Arc::new(DiffuseLight::new(self.emission))
+        } else if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7) {
+            Arc::new(Dielectric::new(if self.ior > 1.0 { self.ior } else { 1.5 }))
+        } else if self.roughness.is_some() || self.metallic.is_some() {
+            let clamp = |v: f64| v.clamp(0.0, 1.0);
+            Arc::new(Principled {
+                base_color: self.diffuse,
+                base_color_texture: diffuse_map,
+                metallic: clamp(self.metallic.unwrap_or(0.0)),
+                roughness: clamp(self.roughness.unwrap_or(0.5)),
+                anisotropic: clamp(self.anisotropy),
+                anisotropic_rotation: self.anisotropy_rotation,
+                sheen: clamp(self.sheen),
+                clearcoat: clamp(self.clearcoat),
+                clearcoat_roughness: clamp(self.clearcoat_roughness),
+                ..Default::default()
+            })
+        } else if self.illum >= 3 && (!self.specular.near_zero() || self.specular_map.is_some()) {
+            // Phong の指数から粗さを見積もる
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            match load(&self.specular_map)? {
+                Some(texture) => Arc::new(Metal::from_texture(texture, fuzz)),
+                None => Arc::new(Metal::new(self.specular, fuzz)),
+            }
+        } else if let Some(texture) = diffuse_map {
+            Arc::new(Lambertian::from_texture(texture))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        })
+    }
+}
+
+/// Meshes and materials loaded from an OBJ file
+pub struct ObjModel {
+    /// One mesh per group and material
+    pub meshes: Vec<ObjMesh>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+pub struct ObjMesh {
+    /// Name of the `o` / `g` statement the faces belong to
+    pub name: String,
+    /// Index into `ObjModel::materials`
+    pub material: Option<usize>,
+    pub mesh: Arc<Mesh>,
+}
+
+impl ObjModel {
+    /// Returns ready to render shapes
+    pub fn into_shapes(self) -> Vec<Box<dyn Shape>> {
+        self.meshes
+            .into_iter()
+            .map(|mesh| Box::new(TriangleMesh::new(mesh.mesh)) as Box<dyn Shape>)
+            .collect()
+    }
+}
+
+/// Key of the vertex a face corner resolves to
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum NormalKey {
+    /// Explicit `vn` index
+    Given(usize),
+    /// Averaged over the faces of a smoothing group
+    Smooth(u32),
+    /// Face normal of the given triangle
+    Flat(usize),
+}
+
+#[derive(Clone, Copy)]
+struct Corner {
+    v: usize,
+    vt: Option<usize>,
+    vn: Option<usize>,
+}
+
+struct FaceGroup {
+    name: String,
+    material: Option<usize>,
+    /// Triangles with their smoothing group
+    triangles: Vec<([Corner; 3], u32)>,
+}
+
+/// Load an OBJ file together with the MTL files it references
+pub fn load_obj(path: impl AsRef<Path>) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut texcoords: Vec<(f64, f64)> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    let mut material_names: HashMap<String, usize> = HashMap::new();
+
+    let mut groups: Vec<FaceGroup> = Vec::new();
+    let mut name = String::from("default");
+    let mut material = None;
+    let mut smoothing = 0;
+
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut tokens = strip_comment(raw).split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_float3(path, line, &args)?),
+            "vn" => normals.push(parse_float3(path, line, &args)?),
+            "vt" => {
+                let uv = parse_floats(path, line, &args, 1)?;
+                texcoords.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(ObjError::syntax(
+                        path,
+                        line,
+                        "face needs at least 3 vertices",
+                    ));
+                }
+                let corners = args
+                    .iter()
+                    .map(|arg| {
+                        parse_corner(arg, positions.len(), texcoords.len(), normals.len())
+                            .map_err(|kind| ObjError::new(path, line, kind))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let same_group = groups
+                    .last()
+                    .is_some_and(|g| g.name == name && g.material == material);
+                if !same_group {
+                    groups.push(FaceGroup {
+                        name: name.clone(),
+                        material,
+                        triangles: Vec::new(),
+                    });
+                }
+                let group = groups.last_mut().unwrap();
+                // 多角形は扇状に三角形分割する
+                for i in 1..corners.len() - 1 {
+                    group
+                        .triangles
+                        .push(([corners[0], corners[i], corners[i + 1]], smoothing));
+                }
+            }
+            "o" | "g" => {
+                name = if args.is_empty() {
+                    String::from("default")
+                } else {
+                    args.join(" ")
+                };
+            }
+            "s" => {
+                smoothing = match args.first() {
+                    None | Some(&"off") => 0,
+                    Some(&"on") => 1,
+                    Some(value) => value
+                        .parse()
+                        .map_err(|_| ObjError::syntax(path, line, "invalid smoothing group"))?,
+                };
+            }
+            "usemtl" => {
+                let material_name = args.join(" ");
+                material = Some(*material_names.get(&material_name).ok_or_else(|| {
+                    ObjError::new(path, line, ObjErrorKind::UnknownMaterial(material_name))
+                })?);
+            }
+            "mtllib" => {
+                for file in &args {
+                    for mtl in load_mtl(dir.join(file))? {
+                        material_names.insert(mtl.name.clone(), materials.len());
+                        materials.push(mtl);
+                    }
+                }
+            }
+            // 線、点、曲面などは描画できないので読み飛ばす
+            _ => {}
+        }
+    }
+
+    let default_material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::full(0.8)));
+    let converted = materials
+        .iter()
+        .map(|m| m.to_material())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let meshes = groups
+        .into_iter()
+        .filter(|group| !group.triangles.is_empty())
+        .map(|group| {
+            let material = group.material.map_or_else(
+                || Arc::clone(&default_material),
+                |i| Arc::clone(&converted[i]),
+            );
+            ObjMesh {
+                mesh: Arc::new(build_mesh(
+                    &group, &positions, &texcoords, &normals, material,
+                )),
+                name: group.name,
+                material: group.material,
+            }
+        })
+        .collect();
+
+    Ok(ObjModel { meshes, materials })
+}
+
+/// Load the materials of an MTL file
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<Vec<ObjMaterial>, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut tokens = strip_comment(raw).split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(ObjError::syntax(path, line, "newmtl needs a name"));
+            }
+            materials.push(ObjMaterial::new(&args.join(" ")));
+            continue;
+        }
+
+        let Some(mtl) = materials.last_mut() else {
+            return Err(ObjError::syntax(
+                path,
+                line,
+                format!("`{}` before any newmtl", keyword),
+            ));
+        };
+        let texture = || {
+            // オプション (-s 1 1 1 など) の後ろの最後の項目をファイル名とみなす
+            args.last()
+                .map(|file| dir.join(file))
+                .ok_or_else(|| ObjError::syntax(path, line, "missing texture file name"))
+        };
+
+        match keyword {
+            "Kd" => mtl.diffuse = parse_color(path, line, &args)?,
+            "Ks" => mtl.specular = parse_color(path, line, &args)?,
+            "Ke" => mtl.emission = parse_color(path, line, &args)?,
+            "Ns" => mtl.shininess = parse_floats(path, line, &args, 1)?[0],
+            "Ni" => mtl.ior = parse_floats(path, line, &args, 1)?[0],
+            "d" => mtl.dissolve = parse_floats(path, line, &args, 1)?[0],
+            "Tr" => mtl.dissolve = 1.0 - parse_floats(path, line, &args, 1)?[0],
+            "illum" => {
+                mtl.illum = args
+                    .first()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| ObjError::syntax(path, line, "invalid illumination model"))?
+            }
+            "Pr" => mtl.roughness = Some(parse_floats(path, line, &args, 1)?[0]),
+            "Pm" => mtl.metallic = Some(parse_floats(path, line, &args, 1)?[0]),
+            "Ps" => mtl.sheen = parse_floats(path, line, &args, 1)?[0],
+            "Pc" => mtl.clearcoat = parse_floats(path, line, &args, 1)?[0],
+            "Pcr" => mtl.clearcoat_roughness = parse_floats(path, line, &args, 1)?[0],
+            "aniso" => mtl.anisotropy = parse_floats(path, line, &args, 1)?[0],
+            "anisor" => mtl.anisotropy_rotation = parse_floats(path, line, &args, 1)?[0],
+            "map_Kd" => mtl.diffuse_map = Some(texture()?),
+            "map_Ks" => mtl.specular_map = Some(texture()?),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|e| ObjError {
+        file: path.to_path_buf(),
+        line: None,
+        kind: ObjErrorKind::Io(e),
+    })
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("")
+}
+
+fn parse_floats(path: &Path, line: usize, args: &[&str], min: usize) -> Result<Vec<f64>, ObjError> {
+    if args.len() < min {
+        return Err(ObjError::syntax(
+            path,
+            line,
+            format!("expected at least {} numbers", min),
+        ));
+    }
+    args.iter()
+        .map(|arg| {
+            arg.parse::<f64>()
+                .map_err(|_| ObjError::syntax(path, line, format!("invalid number `{}`", arg)))
+        })
+        .collect()
+}
+
+fn parse_float3(path: &Path, line: usize, args: &[&str]) -> Result<Float3, ObjError> {
+    let v = parse_floats(path, line, &args[..args.len().min(3)], 3)?;
+    Ok(Float3::new(v[0], v[1], v[2]))
+}
+
+fn parse_color(path: &Path, line: usize, args: &[&str]) -> Result<Color, ObjError> {
+    let v = parse_floats(path, line, args, 1)?;
+    // 成分が一つだけのときは灰色とみなす
+    Ok(match v[..] {
+        [r, g, b, ..] => Color::new(r, g, b),
+        _ => Color::full(v[0]),
+    })
+}
+
+/// Resolve a 1-based or negative (relative) OBJ index
+fn resolve_index(token: &str, len: usize) -> Result<usize, ObjErrorKind> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| ObjErrorKind::Syntax(format!("invalid index `{}`", token)))?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if 0 <= resolved && resolved < len as i64 {
+        Ok(resolved as usize)
+    } else {
+        Err(ObjErrorKind::IndexOutOfRange(index))
+    }
+}
+
+/// Parse `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn parse_corner(arg: &str, nv: usize, nvt: usize, nvn: usize) -> Result<Corner, ObjErrorKind> {
+    let mut parts = arg.split('/');
+    let v = resolve_index(parts.next().unwrap_or(""), nv)?;
+    let vt = match parts.next() {
+        Some(token) if !token.is_empty() => Some(resolve_index(token, nvt)?),
+        _ => None,
+    };
+    let vn = match parts.next() {
+        Some(token) if !token.is_empty() => Some(resolve_index(token, nvn)?),
+        _ => None,
+    };
+    Ok(Corner { v, vt, vn })
+}
+
+fn build_mesh(
+    group: &FaceGroup,
+    positions: &[Point3],
+    texcoords: &[(f64, f64)],
+    normals: &[Vec3],
+    material: Arc<dyn Material>,
+) -> Mesh {
+    let has_uvs = group
+        .triangles
+        .iter()
+        .any(|(c, _)| c.iter().any(|c| c.vt.is_some()));
+    let has_normals = group
+        .triangles
+        .iter()
+        .any(|(c, s)| *s != 0 || c.iter().any(|c| c.vn.is_some()));
+
+    let face_normal = |corners: &[Corner; 3]| {
+        let [p0, p1, p2] = corners.map(|c| positions[c.v]);
+        // 外積の長さは面積に比例するので、そのまま足し合わせると面積で重み付けされる
+        (p1 - p0).cross(p2 - p0)
+    };
+    let normal_key = |corner: &Corner, smoothing: u32, face: usize| match corner.vn {
+        Some(vn) => NormalKey::Given(vn),
+        // 法線を持たないメッシュでは頂点を面の間で共有する
+        None if !has_normals => NormalKey::Smooth(0),
+        None if smoothing != 0 => NormalKey::Smooth(smoothing),
+        None => NormalKey::Flat(face),
+    };
+
+    // スムージンググループの法線は UV の継ぎ目で分かれないよう位置ごとに平均する
+    // 打ち消し合いの判定に使うため、法線の和と一緒に面積の和も持つ
+    let mut smooth_normals: HashMap<(usize, u32), (Vec3, f64)> = HashMap::new();
+    for (face, (corners, smoothing)) in group.triangles.iter().enumerate() {
+        let normal = face_normal(corners);
+        for corner in corners {
+            if let NormalKey::Smooth(s) = normal_key(corner, *smoothing, face) {
+                let (sum, area) = smooth_normals
+                    .entry((corner.v, s))
+                    .or_insert((Vec3::zero(), 0.0));
+                *sum += normal;
+                *area += normal.length();
+            }
+        }
+    }
+
+    let mut vertex_map: HashMap<(usize, Option<usize>, NormalKey), usize> = HashMap::new();
+    let mut mesh_positions = Vec::new();
+    let mut mesh_uvs = Vec::new();
+    let mut mesh_normals = Vec::new();
+    let mut triangles = Vec::with_capacity(group.triangles.len());
+
+    for (face, (corners, smoothing)) in group.triangles.iter().enumerate() {
+        let triangle = corners.map(|corner| {
+            let key = normal_key(&corner, *smoothing, face);
+            *vertex_map
+                .entry((corner.v, corner.vt, key))
+                .or_insert_with(|| {
+                    mesh_positions.push(positions[corner.v]);
+                    mesh_uvs.push(corner.vt.map_or((0.0, 0.0), |vt| texcoords[vt]));
+                    let normal = match key {
+                        NormalKey::Given(vn) => normals[vn],
+                        NormalKey::Smooth(s) => {
+                            // 向きが打ち消し合って平均が0になったら面の法線を使う。
+                            // 小さなモデルでも効くよう面積の和に対する比で判定する
+                            let (sum, area) = smooth_normals[&(corner.v, s)];
+                            if sum.length() > 1e-6 * area {
+                                sum
+                            } else {
+                                face_normal(corners)
+                            }
+                        }
+                        NormalKey::Flat(_) => face_normal(corners),
+                    };
+                    mesh_normals.push(normal);
+                    mesh_positions.len() - 1
+                })
+        });
+        triangles.push(triangle);
+    }
+
+    // 頂点ごとに位置、法線、UV を1つずつ積んだので数は必ずそろっている
+    Mesh {
+        positions: mesh_positions,
+        normals: if has_normals {
+            mesh_normals
+                .into_iter()
+                .map(|n| {
+                    if n.length_squared() > 0.0 {
+                        n.normalize()
+                    } else {
+                        n
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        },
+        uvs: if has_uvs { mesh_uvs } else { Vec::new() },
+        triangles,
+        material,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テストごとに別の一時ディレクトリへファイルを書き出す
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("obj-{}-{}", std::process::id(), test));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn load(test: &str, obj: &str) -> Result<ObjModel, ObjError> {
+        load_obj(write_files(test, &[("model.obj", obj)]).join("model.obj"))
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).near_zero(), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let model = load(
+            "fan",
+            "v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n",
+        )
+        .unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0].mesh;
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+        assert!(mesh.normals.is_empty());
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_end() {
+        let model = load("negative", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").unwrap();
+        assert_eq!(
+            model.meshes[0].mesh.positions[1],
+            Point3::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn errors_name_the_file_and_line() {
+        let dir = write_files("index", &[("model.obj", "v 0 0 0\nv 1 0 0\n\nf 1 2 3\n")]);
+        let error = load_obj(dir.join("model.obj")).err().unwrap();
+        assert_eq!(error.file, dir.join("model.obj"));
+        assert_eq!(error.line, Some(4));
+        assert!(matches!(error.kind, ObjErrorKind::IndexOutOfRange(3)));
+
+        let error = load("syntax", "v 0 zero 0\n").err().unwrap();
+        assert_eq!(error.line, Some(1));
+        assert!(matches!(error.kind, ObjErrorKind::Syntax(_)));
+
+        let error = load("material", "v 0 0 0\nusemtl missing\n").err().unwrap();
+        assert_eq!(error.line, Some(2));
+        assert!(matches!(error.kind, ObjErrorKind::UnknownMaterial(ref name) if name == "missing"));
+    }
+
+    #[test]
+    fn smoothing_groups_average_across_uv_seams() {
+        // 折れ曲がった2枚の三角形。共有する辺で UV が分かれている
+        let source = "v 0 0 0\nv 0 1 0\nv 1 0 0\nv -1 0 1\n\
+                      vt 0 0\nvt 0 1\nvt 1 0\nvt 1 1\nvt 0.5 0.5\n\
+                      s on\nf 1/1 3/3 2/2\nf 1/4 2/5 4/3\n";
+        let model = load("seam", source).unwrap();
+        let mesh = &model.meshes[0].mesh;
+        assert_eq!(mesh.positions.len(), 6);
+        let shared: Vec<Vec3> = (0..mesh.positions.len())
+            .filter(|&i| mesh.positions[i] == Point3::zero())
+            .map(|i| mesh.normals[i])
+            .collect();
+        assert_eq!(shared.len(), 2);
+        // 面積で重み付けした (0, 0, 1) と (1, 0, 1) の和
+        let expected = Vec3::new(1.0, 0.0, 2.0).normalize();
+        assert_close(shared[0], expected);
+        assert_close(shared[1], expected);
+    }
+
+    #[test]
+    fn smoothing_works_at_small_scales() {
+        let source = "v 0 0 0\nv 0 1e-4 0\nv 1e-4 0 0\nv -1e-4 0 1e-4\n\
+                      s 1\nf 1 3 2\nf 1 2 4\n";
+        let mesh = &load("small", source).unwrap().meshes[0].mesh;
+        // 位置を共有するので頂点は4つ
+        assert_eq!(mesh.positions.len(), 4);
+        assert_close(mesh.normals[0], Vec3::new(1.0, 0.0, 2.0).normalize());
+    }
+
+    #[test]
+    fn cancelled_normals_fall_back_to_the_face() {
+        // 同じ三角形を表裏2枚重ねると平均は0になる
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 0\ns 1\nf 1 2 3\nf 4 3 2\n";
+        let mesh = &load("cancel", source).unwrap().meshes[0].mesh;
+        let [a, _, _] = mesh.triangles[0];
+        let [b, _, _] = mesh.triangles[1];
+        assert_close(mesh.normals[a], Vec3::new(0.0, 0.0, 1.0));
+        assert_close(mesh.normals[b], Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn smoothing_off_keeps_faces_flat() {
+        let source = "v 0 0 0\nv 0 1 0\nv 1 0 0\nv -1 0 1\ns 1\nf 1 3 2\ns off\nf 1 2 4\n";
+        let mesh = &load("flat", source).unwrap().meshes[0].mesh;
+        // 平らな面は頂点を共有しない
+        assert_eq!(mesh.positions.len(), 6);
+        assert_close(mesh.normals[0], Vec3::new(0.0, 0.0, 1.0));
+        assert_close(mesh.normals[3], Vec3::new(1.0, 0.0, 1.0).normalize());
+    }
+
+    #[test]
+    fn materials_are_read_from_mtl_files() {
+        let obj = "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n\
+                   usemtl steel\nf 1 3 2\n";
+        // バンプマップは無視されるので、ファイルが無くても読み込める
+        let dir = write_files(
+            "mtl",
+            &[
+                (
+                    "model.mtl",
+                    "newmtl red\nKd 1 0 0\nmap_Bump -bm 0.5 missing.png\n\
+                     newmtl steel\nKs 0.9 0.9 0.9\nillum 3\n",
+                ),
+                ("model.obj", obj),
+            ],
+        );
+        let model = load_obj(dir.join("model.obj")).unwrap();
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.meshes[0].material, Some(0));
+        assert_eq!(model.meshes[1].material, Some(1));
+        assert_eq!(model.materials[0].diffuse, Color::new(1.0, 0.0, 0.0));
+
+        // 鏡面マップは読み込まれる
+        let dir = write_files(
+            "specular-map",
+            &[
+                (
+                    "model.mtl",
+                    "newmtl red\nKd 1 0 0\n\
+                     newmtl steel\nKs 0.9 0.9 0.9\nillum 3\nmap_Ks -s 2 2 2 missing.png\n",
+                ),
+                ("model.obj", obj),
+            ],
+        );
+        let error = load_obj(dir.join("model.obj")).err().unwrap();
+        assert_eq!(error.file, dir.join("missing.png"));
+        assert!(matches!(error.kind, ObjErrorKind::Texture(_)));
+    }
+}
@@ -0,0 +1,819 @@
+//! Analytic shapes besides the sphere and the triangle.
+//!
+//! Shapes with an axis are intersected in a local frame where the axis is +z.
+
+use std::sync::Arc;
+
+use super::{
+    aabb::Aabb, hit_info::HitInfo, material::Material, microfacet::Onb, quaternion::Quaternion,
+    ray::Ray, shapes::Shape, Point3, Vec3, PI2,
+};
+
+/// Origin and orientation of a shape, the local z axis points along `axis`
+struct Placement {
+    origin: Point3,
+    frame: Onb,
+}
+
+impl Placement {
+    fn new(origin: Point3, axis: Vec3) -> Self {
+        Self {
+            origin,
+            frame: Onb::from_normal(axis.normalize()),
+        }
+    }
+
+    /// Returns the origin and direction of `ray` in local coordinates
+    fn to_local(&self, ray: &Ray) -> (Point3, Vec3) {
+        (
+            self.frame.to_local(ray.origin - self.origin),
+            self.frame.to_local(ray.direction),
+        )
+    }
+
+    /// Box in world space containing the local box
+    fn bounds(&self, local: Aabb) -> Aabb {
+        Aabb::from_points((0..8).map(|i| {
+            let corner = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    local.min[axis]
+                } else {
+                    local.max[axis]
+                }
+            };
+            self.origin
+                + self
+                    .frame
+                    .to_world(Point3::new(corner(0), corner(1), corner(2)))
+        }))
+    }
+
+    /// Builds the hit from local normal and tangents
+    fn hit_info(
+        &self,
+        ray: &Ray,
+        t: f64,
+        normal: Vec3,
+        (u, v): (f64, f64),
+        tangents: Option<(Vec3, Vec3)>,
+        material: &Arc<dyn Material>,
+    ) -> HitInfo {
+        let hit = HitInfo::new(
+            ray,
+            t,
+            self.frame.to_world(normal).normalize(),
+            Arc::clone(material),
+        )
+        .with_uv(u, v);
+        match tangents {
+            Some((dpdu, dpdv)) => {
+                hit.with_tangents(self.frame.to_world(dpdu), self.frame.to_world(dpdv))
+            }
+            None => hit,
+        }
+    }
+}
+
+/// Angle around the local z axis as a fraction of a full turn, in [0..1)
+fn turn(x: f64, y: f64) -> f64 {
+    let phi = y.atan2(x) / PI2;
+    if phi < 0.0 {
+        phi + 1.0
+    } else {
+        phi
+    }
+}
+
+/// Derivative of a point at (x, y) with respect to the fraction of a turn around z
+fn around_z(x: f64, y: f64) -> Vec3 {
+    Vec3::new(-y, x, 0.0) * PI2
+}
+
+/// Real roots of `a t^2 + b t + c` in ascending order
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    // 桁落ちしない方の解を先に求め、もう一方は解と係数の関係から出す
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (r0, r1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((r0.min(r1), r0.max(r1)))
+}
+
+/// Largest real root of `x^3 + a x^2 + b x + c`
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    if r * r < q * q * q {
+        // 実数解が3つ。三角関数による解法
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        -2.0 * q.sqrt() * ((theta + PI2) / 3.0).cos() - a / 3.0
+    } else {
+        let s = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let t = if s == 0.0 { 0.0 } else { q / s };
+        s + t - a / 3.0
+    }
+}
+
+/// Real roots of `t^4 + b t^3 + c t^2 + d t + e` by Ferrari's method, polished with Newton
+/// iterations. Returns the roots and their count
+pub fn solve_quartic(b: f64, c: f64, d: f64, e: f64) -> ([f64; 4], usize) {
+    // t = y - b/4 で3次の項を消す
+    let shift = -0.25 * b;
+    let b2 = b * b;
+    let p = c - 0.375 * b2;
+    let q = d - 0.5 * b * c + 0.125 * b2 * b;
+    let r = e - 0.25 * b * d + 0.0625 * b2 * c - 3.0 / 256.0 * b2 * b2;
+
+    let mut roots = [0.0; 4];
+    let mut count = 0;
+    let mut push_quadratic = |qb: f64, qc: f64| {
+        if let Some((y0, y1)) = solve_quadratic(1.0, qb, qc) {
+            roots[count] = y0 + shift;
+            roots[count + 1] = y1 + shift;
+            count += 2;
+        }
+    };
+
+    if q.abs() < 1e-12 {
+        // 複二次式 y^4 + p y^2 + r
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for z in [z0, z1].into_iter().filter(|&z| z >= 0.0) {
+                push_quadratic(0.0, -z);
+            }
+        }
+    } else {
+        // 分解方程式の正の解 m で2つの2次式に分ける
+        let m = largest_cubic_root(p, 0.25 * p * p - r, -0.125 * q * q);
+        if m <= 0.0 {
+            return (roots, 0);
+        }
+        let s = (2.0 * m).sqrt();
+        let half = 0.5 * p + m;
+        push_quadratic(-s, half + q / (2.0 * s));
+        push_quadratic(s, half - q / (2.0 * s));
+    }
+
+    for root in &mut roots[..count] {
+        for _ in 0..2 {
+            let t = *root;
+            let f = (((t + b) * t + c) * t + d) * t + e;
+            let df = ((4.0 * t + 3.0 * b) * t + 2.0 * c) * t + d;
+            if df != 0.0 {
+                *root = t - f / df;
+            }
+        }
+    }
+    (roots, count)
+}
+
+/// Infinite plane, u and v measure the distance from `point` along two tangents
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    frame: Onb,
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: Arc<dyn Material>) -> Self {
+        let normal = normal.normalize();
+        Self {
+            point,
+            normal,
+            frame: Onb::from_normal(normal),
+            material,
+        }
+    }
+}
+
+impl Shape for Plane {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let denom = self.normal.dot(ray.direction);
+        if denom == 0.0 {
+            return None;
+        }
+        let t = self.normal.dot(self.point - ray.origin) / denom;
+        if t <= t0 || t >= t1 {
+            return None;
+        }
+        let offset = ray.at(t) - self.point;
+        Some(
+            HitInfo::new(ray, t, self.normal, Arc::clone(&self.material))
+                .with_uv(offset.dot(self.frame.t), offset.dot(self.frame.b))
+                .with_tangents(self.frame.t, self.frame.b),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// Flat disk, u goes around the center and v from the center to the rim
+pub struct Disk {
+    placement: Placement,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            placement: Placement::new(center, normal),
+            radius: radius.abs(),
+            material,
+        }
+    }
+}
+
+/// Hit on the plane z = `z` of a local ray inside `radius`, for disks and caps
+fn hit_cap(origin: Point3, direction: Vec3, z: f64, radius: f64) -> Option<(f64, Point3)> {
+    if direction.z() == 0.0 {
+        return None;
+    }
+    let t = (z - origin.z()) / direction.z();
+    let p = origin + direction * t;
+    (p.x() * p.x() + p.y() * p.y() <= radius * radius).then_some((t, p))
+}
+
+/// Texture coordinates and tangents of a point on a cap
+fn cap_surface(p: Point3, radius: f64) -> ((f64, f64), Option<(Vec3, Vec3)>) {
+    let r = (p.x() * p.x() + p.y() * p.y()).sqrt();
+    let uv = (turn(p.x(), p.y()), r / radius);
+    // 中心では向きが定まらないので既定の接線に任せる
+    let tangents = (r > 0.0).then(|| {
+        (
+            around_z(p.x(), p.y()),
+            Vec3::new(p.x(), p.y(), 0.0) * (radius / r),
+        )
+    });
+    (uv, tangents)
+}
+
+impl Shape for Disk {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let (origin, direction) = self.placement.to_local(ray);
+        let (t, p) = hit_cap(origin, direction, 0.0, self.radius)?;
+        if t <= t0 || t >= t1 {
+            return None;
+        }
+        let (uv, tangents) = cap_surface(p, self.radius);
+        Some(
+            self.placement
+                .hit_info(ray, t, Vec3::zaxis(), uv, tangents, &self.material),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(
+            self.placement
+                .bounds(Aabb::new(Point3::new(-r, -r, 0.0), Point3::new(r, r, 0.0))),
+        )
+    }
+}
+
+/// Parallelogram spanned by two edges from a corner, u and v run along the edges
+pub struct Quad {
+    corner: Point3,
+    edge_u: Vec3,
+    edge_v: Vec3,
+    normal: Vec3,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(corner: Point3, edge_u: Vec3, edge_v: Vec3, material: Arc<dyn Material>) -> Self {
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            normal: edge_u.cross(edge_v).normalize(),
+            material,
+        }
+    }
+}
+
+impl Shape for Quad {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let denom = self.normal.dot(ray.direction);
+        if denom == 0.0 {
+            return None;
+        }
+        let t = self.normal.dot(self.corner - ray.origin) / denom;
+        if t <= t0 || t >= t1 {
+            return None;
+        }
+        // 辺に沿った座標を面積比で求める
+        let n = self.edge_u.cross(self.edge_v);
+        let w = n / n.length_squared();
+        let q = ray.at(t) - self.corner;
+        let u = w.dot(q.cross(self.edge_v));
+        let v = w.dot(self.edge_u.cross(q));
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+        Some(
+            HitInfo::new(ray, t, self.normal, Arc::clone(&self.material))
+                .with_uv(u, v)
+                .with_tangents(self.edge_u, self.edge_v),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let c = self.corner;
+        Some(Aabb::from_points([
+            c,
+            c + self.edge_u,
+            c + self.edge_v,
+            c + self.edge_u + self.edge_v,
+        ]))
+    }
+}
+
+/// Box, axis-aligned or rotated around its center. Each face is mapped to [0..1] in u and v
+pub struct Cuboid {
+    center: Point3,
+    half_size: Vec3,
+    /// Rotates local directions into the world
+    rotation: Quaternion,
+    material: Arc<dyn Material>,
+}
+
+impl Cuboid {
+    /// Axis-aligned box between two corners
+    pub fn new(min: Point3, max: Point3, material: Arc<dyn Material>) -> Self {
+        Self::oriented((min + max) * 0.5, max - min, Quaternion::unit(), material)
+    }
+
+    /// Box of the given edge lengths, rotated around its center
+    pub fn oriented(
+        center: Point3,
+        size: Vec3,
+        rotation: Quaternion,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            center,
+            half_size: Vec3::from_iter(size.iter().map(|s| 0.5 * s.abs())),
+            rotation: rotation.normalize(),
+            material,
+        }
+    }
+}
+
+impl Shape for Cuboid {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let inverse = self.rotation.conj();
+        let origin = inverse.rotate(ray.origin - self.center);
+        let direction = inverse.rotate(ray.direction);
+        let h = self.half_size;
+
+        let (mut near, mut far) = (f64::NEG_INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            let inv_d = direction[axis].recip();
+            let mut a = (-h[axis] - origin[axis]) * inv_d;
+            let mut b = (h[axis] - origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut a, &mut b);
+            }
+            // NaN (0 * inf) のときは区間を狭めない
+            near = if a > near { a } else { near };
+            far = if b < far { b } else { far };
+        }
+        if near > far {
+            return None;
+        }
+        let t = if t0 < near && near < t1 {
+            near
+        } else if t0 < far && far < t1 {
+            far
+        } else {
+            return None;
+        };
+
+        // 中心からの相対位置が最も大きい軸の面に当たっている
+        let p = origin + direction * t;
+        let axis = (0..3)
+            .max_by(|&i, &j| (p[i] / h[i]).abs().total_cmp(&(p[j] / h[j]).abs()))
+            .unwrap();
+        let mut normal = Vec3::zero();
+        normal[axis] = p[axis].signum();
+        let (a1, a2) = ((axis + 1) % 3, (axis + 2) % 3);
+        let uv = (0.5 * (p[a1] / h[a1] + 1.0), 0.5 * (p[a2] / h[a2] + 1.0));
+        let mut dpdu = Vec3::zero();
+        let mut dpdv = Vec3::zero();
+        dpdu[a1] = 2.0 * h[a1];
+        dpdv[a2] = 2.0 * h[a2];
+
+        Some(
+            HitInfo::new(
+                ray,
+                t,
+                self.rotation.rotate(normal),
+                Arc::clone(&self.material),
+            )
+            .with_uv(uv.0, uv.1)
+            .with_tangents(self.rotation.rotate(dpdu), self.rotation.rotate(dpdv)),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let h = self.half_size;
+        Some(Aabb::from_points((0..8).map(|i| {
+            let corner = Vec3::new(
+                if i & 1 == 0 { -h.x() } else { h.x() },
+                if i & 2 == 0 { -h.y() } else { h.y() },
+                if i & 4 == 0 { -h.z() } else { h.z() },
+            );
+            self.center + self.rotation.rotate(corner)
+        })))
+    }
+}
+
+/// Cylinder standing on the center of its base, optionally closed by disks at both ends.
+///
+/// On the side u goes around the axis and v from the base to the top.
+pub struct Cylinder {
+    placement: Placement,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            placement: Placement::new(base, axis),
+            radius: radius.abs(),
+            height: height.abs(),
+            capped: true,
+            material,
+        }
+    }
+
+    /// Leave the ends open
+    pub fn open(mut self) -> Self {
+        self.capped = false;
+        self
+    }
+}
+
+impl Shape for Cylinder {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let (o, d) = self.placement.to_local(ray);
+        let (r, h) = (self.radius, self.height);
+        let in_range = |t: f64| t0 < t && t < t1;
+
+        // 側面と蓋のうち最も近い交点を選ぶ
+        let mut best: Option<(f64, Point3, bool)> = None;
+        let mut consider = |t: f64, p: Point3, side: bool| {
+            if in_range(t) && best.is_none_or(|(b, _, _)| t < b) {
+                best = Some((t, p, side));
+            }
+        };
+        let a = d.x() * d.x() + d.y() * d.y();
+        if a > 0.0 {
+            let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+            let c = o.x() * o.x() + o.y() * o.y() - r * r;
+            if let Some((ta, tb)) = solve_quadratic(a, b, c) {
+                for t in [ta, tb] {
+                    let p = o + d * t;
+                    if (0.0..=h).contains(&p.z()) {
+                        consider(t, p, true);
+                    }
+                }
+            }
+        }
+        if self.capped {
+            for z in [0.0, h] {
+                if let Some((t, p)) = hit_cap(o, d, z, r) {
+                    consider(t, p, false);
+                }
+            }
+        }
+
+        let (t, p, side) = best?;
+        let (normal, uv, tangents) = if side {
+            (
+                Vec3::new(p.x(), p.y(), 0.0) / r,
+                (turn(p.x(), p.y()), p.z() / h),
+                Some((around_z(p.x(), p.y()), Vec3::new(0.0, 0.0, h))),
+            )
+        } else {
+            let (uv, tangents) = cap_surface(p, r);
+            let normal = if p.z() > 0.5 * h {
+                Vec3::zaxis()
+            } else {
+                -Vec3::zaxis()
+            };
+            (normal, uv, tangents)
+        };
+        Some(
+            self.placement
+                .hit_info(ray, t, normal, uv, tangents, &self.material),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.placement.bounds(Aabb::new(
+            Point3::new(-r, -r, 0.0),
+            Point3::new(r, r, self.height),
+        )))
+    }
+}
+
+/// Cone standing on the center of its base with the apex at `height` along the axis,
+/// optionally closed by a disk at the base.
+///
+/// On the side u goes around the axis and v from the base to the apex.
+pub struct Cone {
+    placement: Placement,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(
+        base: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            placement: Placement::new(base, axis),
+            radius: radius.abs(),
+            height: height.abs(),
+            capped: true,
+            material,
+        }
+    }
+
+    /// Leave the base open
+    pub fn open(mut self) -> Self {
+        self.capped = false;
+        self
+    }
+}
+
+impl Shape for Cone {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let (o, d) = self.placement.to_local(ray);
+        let (r, h) = (self.radius, self.height);
+        let k2 = (r / h).powi(2);
+        let in_range = |t: f64| t0 < t && t < t1;
+
+        let mut best: Option<(f64, Point3, bool)> = None;
+        let mut consider = |t: f64, p: Point3, side: bool| {
+            if in_range(t) && best.is_none_or(|(b, _, _)| t < b) {
+                best = Some((t, p, side));
+            }
+        };
+        // x^2 + y^2 = k^2 (h - z)^2 のうち 0 <= z <= h の部分
+        let hz = h - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y() + k2 * hz * d.z());
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * hz * hz;
+        if let Some((ta, tb)) = solve_quadratic(a, b, c) {
+            for t in [ta, tb] {
+                let p = o + d * t;
+                if (0.0..=h).contains(&p.z()) {
+                    consider(t, p, true);
+                }
+            }
+        }
+        if self.capped {
+            if let Some((t, p)) = hit_cap(o, d, 0.0, r) {
+                consider(t, p, false);
+            }
+        }
+
+        let (t, p, side) = best?;
+        let (normal, uv, tangents) = if side {
+            let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+            let normal = Vec3::new(p.x(), p.y(), k2 * (h - p.z()));
+            // 頂点では向きが定まらないので既定の接線に任せる
+            let tangents = (rho > 0.0).then(|| {
+                (
+                    around_z(p.x(), p.y()),
+                    Vec3::new(-p.x() * r / rho, -p.y() * r / rho, h),
+                )
+            });
+            (normal, (turn(p.x(), p.y()), p.z() / h), tangents)
+        } else {
+            let (uv, tangents) = cap_surface(p, r);
+            (-Vec3::zaxis(), uv, tangents)
+        };
+        Some(
+            self.placement
+                .hit_info(ray, t, normal, uv, tangents, &self.material),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.placement.bounds(Aabb::new(
+            Point3::new(-r, -r, 0.0),
+            Point3::new(r, r, self.height),
+        )))
+    }
+}
+
+/// Torus around `axis`, `major_radius` is the distance from the center to the middle of the
+/// tube and `minor_radius` the radius of the tube.
+///
+/// u goes around the axis and v around the tube, starting on the outside.
+pub struct Torus {
+    placement: Placement,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            placement: Placement::new(center, axis),
+            major_radius: major_radius.abs(),
+            minor_radius: minor_radius.abs(),
+            material,
+        }
+    }
+}
+
+impl Shape for Torus {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let (o, d) = self.placement.to_local(ray);
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+
+        // 方向を正規化し、外接球の手前まで原点を進めて係数の桁をそろえる
+        let length = d.length();
+        let d = d / length;
+        let bound = big_r + small_r;
+        let f = o.dot(d);
+        let c = o.length_squared() - bound * bound;
+        let discriminant = f * f - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let skip = (-f - discriminant.sqrt()).max(0.0);
+        let o = o + d * skip;
+
+        let f = o.dot(d);
+        let e = o.length_squared() + big_r * big_r - small_r * small_r;
+        let r4 = 4.0 * big_r * big_r;
+        let (roots, count) = solve_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * e - r4 * (d.x() * d.x() + d.y() * d.y()),
+            4.0 * e * f - 2.0 * r4 * (o.x() * d.x() + o.y() * d.y()),
+            e * e - r4 * (o.x() * o.x() + o.y() * o.y()),
+        );
+        let s = roots[..count]
+            .iter()
+            .copied()
+            .filter(|&s| {
+                let t = (skip + s) / length;
+                t0 < t && t < t1
+            })
+            .min_by(f64::total_cmp)?;
+        let t = (skip + s) / length;
+
+        let p = o + d * s;
+        let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+        // 陰関数の勾配
+        let normal = p * (p.length_squared() - big_r * big_r - small_r * small_r)
+            + Vec3::new(0.0, 0.0, 2.0 * big_r * big_r * p.z());
+        let uv = (turn(p.x(), p.y()), turn(rho - big_r, p.z()));
+        let tangents = (rho > 0.0).then(|| {
+            (
+                around_z(p.x(), p.y()),
+                Vec3::new(-p.z() * p.x() / rho, -p.z() * p.y() / rho, rho - big_r) * PI2,
+            )
+        });
+        Some(
+            self.placement
+                .hit_info(ray, t, normal, uv, tangents, &self.material),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.major_radius + self.minor_radius;
+        let h = self.minor_radius;
+        Some(
+            self.placement
+                .bounds(Aabb::new(Point3::new(-r, -r, -h), Point3::new(r, r, h))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracing::{material::Lambertian, Color};
+
+    /// 解を昇順に並べ、期待値と比べる
+    fn assert_roots(b: f64, c: f64, d: f64, e: f64, expected: &[f64]) {
+        let (mut roots, count) = solve_quartic(b, c, d, e);
+        assert_eq!(count, expected.len(), "roots: {:?}", &roots[..count]);
+        roots[..count].sort_by(f64::total_cmp);
+        for (root, want) in roots[..count].iter().zip(expected) {
+            assert!((root - want).abs() < 1e-9, "{root} != {want}");
+        }
+    }
+
+    #[test]
+    fn cubic_largest_root() {
+        // (x - 1)(x - 2)(x - 3)
+        assert!((largest_cubic_root(-6.0, 11.0, -6.0) - 3.0).abs() < 1e-9);
+        // (x - 2)(x^2 + 1)
+        assert!((largest_cubic_root(-2.0, 1.0, -2.0) - 2.0).abs() < 1e-9);
+        // x^3
+        assert_eq!(largest_cubic_root(0.0, 0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn quartic_four_roots() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        assert_roots(-10.0, 35.0, -50.0, 24.0, &[1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn quartic_two_roots() {
+        // (t - 1)(t + 2)(t^2 + 1)
+        assert_roots(1.0, -1.0, 1.0, -2.0, &[-2.0, 1.0]);
+    }
+
+    #[test]
+    fn quartic_biquadratic() {
+        // (t^2 - 1)(t^2 - 4)
+        assert_roots(0.0, -5.0, 0.0, 4.0, &[-2.0, -1.0, 1.0, 2.0]);
+        // (t^2 - 4)(t^2 + 1)
+        assert_roots(0.0, -3.0, 0.0, -4.0, &[-2.0, 2.0]);
+    }
+
+    #[test]
+    fn quartic_no_roots() {
+        // (t^2 + 1)(t^2 + 4)
+        assert_roots(0.0, 5.0, 0.0, 4.0, &[]);
+        // ((t - 1)^2 + 1)((t + 2)^2 + 1)
+        assert_roots(2.0, 3.0, 2.0, 10.0, &[]);
+    }
+
+    #[test]
+    fn torus_grazing_ray() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let torus = Torus::new(
+            Point3::zero(),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            0.25,
+            material,
+        );
+
+        // 外周の赤道をかすめる光線
+        let y = 1.25 - 1e-4;
+        let ray = Ray::new(Point3::new(-5.0, y, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = torus
+            .hit(&ray, 1e-6, f64::INFINITY)
+            .expect("grazing ray should hit");
+        let expected = 5.0 - (1.25 * 1.25 - y * y).sqrt();
+        assert!(
+            (hit.length - expected).abs() < 1e-3,
+            "{} != {expected}",
+            hit.length
+        );
+
+        // 少しだけ外れる光線
+        let ray = Ray::new(
+            Point3::new(-5.0, 1.25 + 1e-4, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+        assert!(torus.hit(&ray, 1e-6, f64::INFINITY).is_none());
+    }
+}
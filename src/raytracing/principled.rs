@@ -0,0 +1,263 @@
+use std::{borrow::Cow, sync::Arc};
+
+use super::{
+    hit_info::HitInfo,
+    material::{texture_value, Material, ScatterInfo},
+    microfacet::{cosine_hemisphere, fresnel_conductor, fresnel_schlick, Ggx, Onb},
+    ray::Ray,
+    rng::random,
+    texture::Texture,
+    Color, Vec3, FRAC_1_PI, PI2,
+};
+
+/// Physically based material with the Disney / Principled parameter set.
+///
+/// Layers a Burley diffuse and sheen base, a GGX specular lobe that blends from dielectric
+/// to metal with `metallic`, and a GGX clearcoat on top. All parameters are in [0..1].
+#[derive(Clone)]
+pub struct Principled {
+    pub base_color: Color,
+    /// Replaces `base_color` when given
+    pub base_color_texture: Option<Arc<dyn Texture>>,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub anisotropic: f64,
+    /// Rotation of the anisotropy direction, in fractions of a full turn
+    pub anisotropic_rotation: f64,
+    /// Reflectance of the dielectric specular, 0.5 is 4%
+    pub specular: f64,
+    /// Tints the dielectric specular towards the base color
+    pub specular_tint: f64,
+    pub sheen: f64,
+    pub sheen_tint: f64,
+    pub clearcoat: f64,
+    pub clearcoat_roughness: f64,
+    /// Complex index of refraction `(eta, k)` of the metal, the base color is used when None
+    pub conductor: Option<(Color, Color)>,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self {
+            base_color: Color::full(0.8),
+            base_color_texture: None,
+            metallic: 0.0,
+            roughness: 0.5,
+            anisotropic: 0.0,
+            anisotropic_rotation: 0.0,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            conductor: None,
+        }
+    }
+}
+
+/// Lobes sampled by `Principled::scatter`
+#[derive(Clone, Copy)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+}
+
+impl Principled {
+    pub fn new(base_color: Color) -> Self {
+        Self {
+            base_color,
+            ..Default::default()
+        }
+    }
+
+    /// Rough metal described by its complex index of refraction
+    pub fn conductor(eta: Color, k: Color, roughness: f64) -> Self {
+        Self {
+            base_color: Color::one(),
+            metallic: 1.0,
+            roughness,
+            conductor: Some((eta, k)),
+            ..Default::default()
+        }
+    }
+
+    /// Parameters at the hit point, with the textures looked up
+    fn at(&self, hit: &HitInfo) -> Cow<'_, Self> {
+        match &self.base_color_texture {
+            Some(texture) => Cow::Owned(Self {
+                base_color: texture_value(texture.as_ref(), hit),
+                base_color_texture: None,
+                ..self.clone()
+            }),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    fn specular_ggx(&self) -> Ggx {
+        Ggx::from_roughness(self.roughness, self.anisotropic)
+    }
+
+    fn clearcoat_ggx(&self) -> Ggx {
+        Ggx::from_roughness(self.clearcoat_roughness, 0.0)
+    }
+
+    /// Shading frame following the u direction of the surface, turned by the anisotropy
+    /// rotation
+    fn frame(&self, hit: &HitInfo) -> Onb {
+        Onb::from_normal_tangent(hit.facing_normal(), hit.dpdu)
+            .rotate(self.anisotropic_rotation * PI2)
+    }
+
+    /// Base color with its luminance removed
+    fn tint(&self) -> Color {
+        let luminance = self.base_color.luminance();
+        if luminance > 0.0 {
+            self.base_color / luminance
+        } else {
+            Color::one()
+        }
+    }
+
+    /// Probabilities of sampling each lobe
+    fn lobe_weights(&self) -> [(Lobe, f64); 3] {
+        let diffuse = (1.0 - self.metallic) * self.base_color.luminance().clamp(0.05, 1.0);
+        let specular = 0.25 + 0.75 * self.metallic;
+        let clearcoat = 0.25 * self.clearcoat;
+        let total = diffuse + specular + clearcoat;
+        [
+            (Lobe::Diffuse, diffuse / total),
+            (Lobe::Specular, specular / total),
+            (Lobe::Clearcoat, clearcoat / total),
+        ]
+    }
+
+    /// BSDF times cosine for local directions
+    fn eval_local(&self, wo: Vec3, wi: Vec3) -> Color {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::zero();
+        }
+        let h = (wo + wi).normalize();
+        let cos_d = wi.dot(h);
+        let schlick_weight = |c: f64| (1.0 - c).clamp(0.0, 1.0).powi(5);
+
+        // Burley の拡散反射。粗いほど縁で明るくなる
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fd = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z()))
+            * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z()));
+        let diffuse = self.base_color * (fd * FRAC_1_PI);
+        let sheen =
+            Color::one().lerp(self.tint(), self.sheen_tint) * (self.sheen * schlick_weight(cos_d));
+        let base = (diffuse + sheen) * (1.0 - self.metallic);
+
+        // 誘電体と金属のフレネルを metallic で混ぜる
+        let dielectric_f0 =
+            Color::one().lerp(self.tint(), self.specular_tint) * (0.08 * self.specular);
+        let metal_fresnel = match self.conductor {
+            Some((eta, k)) => fresnel_conductor(cos_d, eta, k),
+            None => fresnel_schlick(self.base_color, cos_d),
+        };
+        let fresnel = fresnel_schlick(dielectric_f0, cos_d) * (1.0 - self.metallic)
+            + metal_fresnel * self.metallic;
+        let ggx = self.specular_ggx();
+        let specular = fresnel * (ggx.d(h) * ggx.g2(wo, wi) / (4.0 * wo.z() * wi.z()));
+
+        let clearcoat = if self.clearcoat > 0.0 {
+            let ggx = self.clearcoat_ggx();
+            let fresnel = fresnel_schlick(Color::full(0.04), cos_d);
+            fresnel * (0.25 * self.clearcoat * ggx.d(h) * ggx.g2(wo, wi) / (4.0 * wo.z() * wi.z()))
+        } else {
+            Color::zero()
+        };
+
+        (base + specular + clearcoat) * wi.z()
+    }
+
+    fn pdf_local(&self, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        self.lobe_weights()
+            .iter()
+            .map(|&(lobe, weight)| {
+                weight
+                    * match lobe {
+                        Lobe::Diffuse => wi.z() * FRAC_1_PI,
+                        Lobe::Specular => self.specular_ggx().reflection_pdf(wo, wi),
+                        Lobe::Clearcoat if self.clearcoat > 0.0 => {
+                            self.clearcoat_ggx().reflection_pdf(wo, wi)
+                        }
+                        Lobe::Clearcoat => 0.0,
+                    }
+            })
+            .sum()
+    }
+
+    fn sample_local(&self, wo: Vec3) -> Option<Vec3> {
+        let mut u = random::<f64>();
+        let lobe = self
+            .lobe_weights()
+            .into_iter()
+            .find(|&(_, weight)| {
+                u -= weight;
+                u < 0.0
+            })
+            .map_or(Lobe::Specular, |(lobe, _)| lobe);
+
+        let u = (random(), random());
+        let wi = match lobe {
+            Lobe::Diffuse => cosine_hemisphere(u),
+            Lobe::Specular => reflect(wo, self.specular_ggx().sample_visible_normal(wo, u)),
+            Lobe::Clearcoat => reflect(wo, self.clearcoat_ggx().sample_visible_normal(wo, u)),
+        };
+        (wi.z() > 0.0).then_some(wi)
+    }
+}
+
+/// Mirror `wo` about the microfacet normal `m`
+fn reflect(wo: Vec3, m: Vec3) -> Vec3 {
+    m * (2.0 * wo.dot(m)) - wo
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, hit: &HitInfo) -> Option<ScatterInfo> {
+        let params = self.at(hit);
+        let frame = params.frame(hit);
+        let wo = frame.to_local(-ray.direction.normalize());
+        let wi = params.sample_local(wo)?;
+
+        // どのローブから選んでも、全ローブを合わせた密度で重みを求める
+        let pdf = params.pdf_local(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let albedo = params.eval_local(wo, wi) / pdf;
+        Some(ScatterInfo::new(
+            Ray::new(hit.position, frame.to_world(wi)),
+            albedo,
+        ))
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitInfo, direction: Vec3) -> Color {
+        let params = self.at(hit);
+        let frame = params.frame(hit);
+        params.eval_local(
+            frame.to_local(-ray.direction.normalize()),
+            frame.to_local(direction),
+        )
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitInfo, direction: Vec3) -> f64 {
+        let params = self.at(hit);
+        let frame = params.frame(hit);
+        params.pdf_local(
+            frame.to_local(-ray.direction.normalize()),
+            frame.to_local(direction),
+        )
+    }
+}
@@ -1,5 +1,6 @@
 use super::Vec3;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Quaternion(Vec3, f64);
 
 impl Quaternion {
@@ -81,7 +82,7 @@ impl Quaternion {
         let x = (w1 * x2 + y1 * z2) - (z1 * y2);
         let y = (w1 * y2 + z1 * x2) - (x1 * z2);
         let z = (w1 * z2 + x1 * y2) - (y1 * x2);
-        let w = (x1 * x2 + y1 * y2) - (z1 * z2);
+        let w = (x1 * x2 + y1 * y2) + (z1 * z2);
         Vec3::new(
             ((w * x1 + x * w1) - y * z1) + z * y1,
             ((w * y1 + y * w1) - z * x1) + x * z1,
@@ -98,7 +99,7 @@ impl std::ops::Mul<Quaternion> for Quaternion {
         let [x2, y2, z2, w2] = rhs.to_array();
         Quaternion::new(
             w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
-            w1 * y2 + y2 * w2 + z1 * x2 - x1 * z2,
+            w1 * y2 + y1 * w2 + z1 * x2 - x1 * z2,
             w1 * z2 + z1 * w2 + x1 * y2 - y1 * x2,
             w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
         )
@@ -1,6 +1,25 @@
-use image::{ImageBuffer, Rgb, RgbImage};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
 
-use super::{camera::Camera, ray::Ray, Color};
+use super::{
+    camera::Camera,
+    environment::Environment,
+    film::Film,
+    hit_info::HitInfo,
+    light::Light,
+    material::Material,
+    medium::{HenyeyGreenstein, HomogeneousMedium, Medium},
+    ray::Ray,
+    rng::{self, random},
+    sampler::{self, Sampler, SamplerKind},
+    tile::{self, Tile, TileOrder},
+    Color, Point3, Vec3, EPS,
+};
 
 // const IMAGE_WIDTH: u32 = 200;
 // const IMAGE_HEIGHT: u32 = 100;
@@ -8,10 +27,41 @@ use super::{camera::Camera, ray::Ray, Color};
 const IMAGE_WIDTH: u32 = 1920;
 const IMAGE_HEIGHT: u32 = 1080;
 
-const BACKUP_FILENAME: &str = "render.png";
+pub const SAMPLES_PER_PIXEL: u32 = 16;
+pub const MAX_RAY_BOUNCE_DEPTH: usize = 50;
+pub const RUSSIAN_ROULETTE_DEPTH: usize = 3;
+
+/// Shadow rays stop this fraction short of the light so they don't hit the light itself
+const SHADOW_EPS: f64 = 1e-4;
+
+pub const TILE_SIZE: u32 = 32;
+
+pub const BACKUP_FILENAME: &str = "render.png";
+
+/// Image and sampling settings a scene is rendered with
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub spp: u32,
+    pub sampler: SamplerKind,
+    pub max_depth: usize,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: IMAGE_WIDTH,
+            height: IMAGE_HEIGHT,
+            spp: SAMPLES_PER_PIXEL,
+            sampler: SamplerKind::default(),
+            max_depth: MAX_RAY_BOUNCE_DEPTH,
+        }
+    }
+}
 
 pub trait Scene {
-    fn camera(&self) -> Camera;
+    fn camera(&self) -> Box<dyn Camera>;
     fn trace(&self, ray: Ray) -> Color;
     fn width(&self) -> u32 {
         IMAGE_WIDTH
@@ -19,28 +69,533 @@ pub trait Scene {
     fn height(&self) -> u32 {
         IMAGE_HEIGHT
     }
+    fn spp(&self) -> u32 {
+        SAMPLES_PER_PIXEL
+    }
+    fn sampler(&self) -> SamplerKind {
+        SamplerKind::default()
+    }
     fn aspect(&self) -> f64 {
         self.width() as f64 / self.height() as f64
     }
 }
 
-pub fn render(scene: impl Scene + Sync) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-    let camera = scene.camera();
+/// What the integrator needs to know about a scene to follow rays through it
+pub trait World {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo>;
+    /// Light arriving from outside the scene
+    fn environment(&self) -> &dyn Environment;
+    /// Light sources sampled directly by the integrator
+    fn lights(&self) -> &[Box<dyn Light>] {
+        &[]
+    }
+    /// Medium the camera is in, None for vacuum
+    fn medium(&self) -> Option<Arc<dyn Medium>> {
+        None
+    }
+}
+
+/// How light sampling and BSDF sampling are weighted against each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MisHeuristic {
+    Balance,
+    #[default]
+    Power,
+}
+
+impl MisHeuristic {
+    /// Weight of a sample drawn with density `pdf` when `other_pdf` could also have drawn it
+    pub fn weight(self, pdf: f64, other_pdf: f64) -> f64 {
+        let (a, b) = match self {
+            MisHeuristic::Balance => (pdf, other_pdf),
+            MisHeuristic::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+        if a + b <= 0.0 {
+            0.0
+        } else {
+            a / (a + b)
+        }
+    }
+}
+
+/// Monte Carlo path tracing integrator
+pub struct PathTracer {
+    /// Maximum number of bounces a path may take
+    pub max_depth: usize,
+    /// Number of bounces after which paths are terminated by russian roulette
+    pub russian_roulette_depth: usize,
+    /// Multiple importance sampling heuristic for direct lighting
+    pub heuristic: MisHeuristic,
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self::new(MAX_RAY_BOUNCE_DEPTH)
+    }
+}
+
+impl PathTracer {
+    pub const fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            russian_roulette_depth: RUSSIAN_ROULETTE_DEPTH,
+            heuristic: MisHeuristic::Power,
+        }
+    }
+
+    pub fn with_russian_roulette(mut self, depth: usize) -> Self {
+        self.russian_roulette_depth = depth;
+        self
+    }
+
+    pub fn with_heuristic(mut self, heuristic: MisHeuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    /// Returns the radiance arriving along `ray`
+    pub fn trace(&self, world: &(impl World + ?Sized), ray: Ray) -> Color {
+        let lights = world.lights();
+        let environment = world.environment();
+        let strategies = light_strategies(world);
+        let mut radiance = Color::zero();
+        let mut throughput = Color::one();
+        let mut ray = ray;
+        let mut medium = world.medium();
+        // 直前に散乱した点。媒質の境界を通り抜けてもレイの始点と違ってこちらは変わらない
+        let mut vertex = ray.origin;
+        // カメラからのレイと鏡面反射の後は光源を直接サンプリングしていない
+        let mut specular_bounce = true;
+        // 直前の散乱方向を BSDF が選ぶ確率密度
+        let mut bsdf_pdf = 0.0;
+        // 表面下散乱のランダムウォークが形状から出た点
+        let mut walk_exit: Option<Box<HitInfo>> = None;
+
+        for depth in 0..=self.max_depth {
+            let event = match walk_exit.take() {
+                Some(hit) => Event::Surface(hit),
+                None => next_event(world, &mut ray, &mut medium, &mut throughput),
+            };
+            match event {
+                Event::Escaped => {
+                    let weight = if !specular_bounce && environment.is_sampled() {
+                        let light_pdf = environment.pdf(ray.direction) / strategies as f64;
+                        self.heuristic.weight(bsdf_pdf, light_pdf)
+                    } else {
+                        1.0
+                    };
+                    radiance += throughput * environment.radiance(ray.direction) * weight;
+                    break;
+                }
+                Event::Medium(position, phase) => {
+                    if depth == self.max_depth {
+                        break;
+                    }
+                    let direction = ray.direction.normalize();
+                    if strategies > 0 {
+                        radiance += throughput
+                            * self.sample_light(
+                                world,
+                                position,
+                                |wi| Color::full(phase.p(direction, wi)),
+                                |wi| phase.p(direction, wi),
+                                |_| medium.clone(),
+                            );
+                    }
+                    // 位相関数どおりにサンプリングするので重みは1
+                    let (scattered, pdf) = phase.sample(direction, (random(), random()));
+                    specular_bounce = false;
+                    bsdf_pdf = pdf;
+                    vertex = position;
+                    ray = Ray::new(position, scattered);
+                }
+                Event::Surface(hit) => {
+                    // 直前の点で光源サンプリングもしていた場合は、両方の戦略の重みで分け合う。
+                    // 光源として登録されていない発光面はサンプリングされないので重みは1
+                    let emitted = hit.material.emitted(&ray, &hit);
+                    if emitted.max_element() > 0.0 {
+                        let light_pdf = match hit.light_id {
+                            Some(id) if !specular_bounce => {
+                                lights[id].pdf(vertex, ray.direction) / strategies as f64
+                            }
+                            _ => 0.0,
+                        };
+                        let weight = if light_pdf > 0.0 {
+                            self.heuristic.weight(bsdf_pdf, light_pdf)
+                        } else {
+                            1.0
+                        };
+                        radiance += throughput * emitted * weight;
+                    }
+
+                    if depth == self.max_depth {
+                        break;
+                    }
+                    specular_bounce = hit.material.is_specular();
+                    if !specular_bounce && strategies > 0 {
+                        radiance += throughput
+                            * self.sample_light(
+                                world,
+                                hit.position,
+                                |wi| hit.material.eval(&ray, &hit, wi),
+                                |wi| hit.material.pdf(&ray, &hit, wi),
+                                |wi| match &hit.medium_interface {
+                                    Some(interface) => interface.towards(wi, hit.direction),
+                                    None => medium.clone(),
+                                },
+                            );
+                    }
+
+                    let Some(scatter) = hit.material.scatter(&ray, &hit) else {
+                        break;
+                    };
+                    if !specular_bounce {
+                        bsdf_pdf = hit
+                            .material
+                            .pdf(&ray, &hit, scatter.ray.direction.normalize());
+                    }
+                    if let Some(interface) = &hit.medium_interface {
+                        medium = interface.towards(scatter.ray.direction, hit.direction);
+                    }
+                    throughput *= scatter.albedo;
+                    vertex = hit.position;
+                    ray = scatter.ray;
+
+                    // 形状の内側へ入ったら出ていく点まで歩き、次はそこで散乱させる
+                    if ray.direction.dot(hit.direction) < 0.0 {
+                        if let Some(interior) = hit.material.subsurface(&hit) {
+                            let Some((last, exit)) =
+                                random_walk(world, &hit, ray, &interior, &mut throughput)
+                            else {
+                                break;
+                            };
+                            vertex = last.origin;
+                            ray = last;
+                            walk_exit = Some(Box::new(exit));
+                        }
+                    }
+                }
+            }
+
+            // 寄与の小さいパスを確率的に打ち切り、生き残ったパスの重みを上げて不偏性を保つ
+            if depth >= self.russian_roulette_depth {
+                let survive = throughput.max_element().clamp(0.05, 0.95);
+                if random::<f64>() >= survive {
+                    break;
+                }
+                throughput = throughput / survive;
+            }
+        }
+
+        radiance
+    }
+
+    /// Estimate the direct light at `position` by sampling one light, or the environment,
+    /// chosen at random. `f` is the BSDF or phase function times the cosine term, `pdf` the
+    /// density with which the path would have picked the direction and `medium` the medium
+    /// a ray leaving in a direction starts in
+    fn sample_light(
+        &self,
+        world: &(impl World + ?Sized),
+        position: Point3,
+        f: impl Fn(Vec3) -> Color,
+        pdf: impl Fn(Vec3) -> f64,
+        medium: impl Fn(Vec3) -> Option<Arc<dyn Medium>>,
+    ) -> Color {
+        let lights = world.lights();
+        let strategies = light_strategies(world);
+        let index = ((random::<f64>() * strategies as f64) as usize).min(strategies - 1);
+        let u = (random(), random());
+        let (sample, is_delta) = match lights.get(index) {
+            Some(light) => (light.sample(position, u), light.is_delta()),
+            None => (world.environment().sample(u), false),
+        };
+        let Some(sample) = sample else {
+            return Color::zero();
+        };
+        if sample.pdf <= 0.0 {
+            return Color::zero();
+        }
+        let f = f(sample.direction);
+        if f.max_element() <= 0.0 {
+            return Color::zero();
+        }
+
+        // 光源までの間に遮るものがあれば影になり、媒質があれば減衰する
+        let tr = transmittance(
+            world,
+            Ray::new(position, sample.direction),
+            sample.distance * (1.0 - SHADOW_EPS),
+            medium(sample.direction),
+        );
+        if tr.max_element() <= 0.0 {
+            return Color::zero();
+        }
+
+        // 大きさのない光源は BSDF のサンプリングでは当たらないので重みは1
+        let light_pdf = sample.pdf / strategies as f64;
+        let weight = if is_delta {
+            1.0
+        } else {
+            self.heuristic.weight(light_pdf, pdf(sample.direction))
+        };
+        f * tr * sample.radiance * (weight / light_pdf)
+    }
+}
+
+/// Where a path meets the scene next
+enum Event {
+    Surface(Box<HitInfo>),
+    /// Scattering inside a medium at a point, with the phase function there
+    Medium(Point3, HenyeyGreenstein),
+    Escaped,
+}
+
+/// Follow `ray` to the next surface or scattering in a medium, passing through the
+/// boundaries of media. The throughput takes the weight of the medium sampling and `ray` and
+/// `medium` are moved past the boundaries crossed
+fn next_event(
+    world: &(impl World + ?Sized),
+    ray: &mut Ray,
+    medium: &mut Option<Arc<dyn Medium>>,
+    throughput: &mut Color,
+) -> Event {
+    loop {
+        // 自己交差を避けるため EPS 未満の交差は無視する
+        let hit = world.hit(ray, EPS, f64::MAX);
+        if let Some(current) = medium.as_ref() {
+            let t_max = hit.as_ref().map_or(f64::INFINITY, |hit| hit.length);
+            let sample = current.sample(ray, t_max);
+            *throughput *= sample.weight;
+            if let Some(t) = sample.scatter {
+                return Event::Medium(ray.at(t), *current.phase());
+            }
+        }
+        let Some(hit) = hit else {
+            return Event::Escaped;
+        };
+        if !hit.material.is_interface() {
+            return Event::Surface(Box::new(hit));
+        }
+        if let Some(interface) = &hit.medium_interface {
+            *medium = interface.towards(ray.direction, hit.direction);
+        }
+        *ray = Ray::new(hit.position, ray.direction);
+    }
+}
+
+/// Longest walk through a subsurface material, paths still inside after it are dropped
+const MAX_WALK_STEPS: usize = 256;
+
+/// Follow `ray`, scattered into the shape of a subsurface material at `entry`, through the
+/// inside as it scatters in `interior`. Returns the last segment of the walk with the point
+/// where it leaves the shape, None if the light is absorbed or no surface of the material is
+/// ahead. The throughput takes the weight of the walk
+fn random_walk(
+    world: &(impl World + ?Sized),
+    entry: &HitInfo,
+    mut ray: Ray,
+    interior: &HomogeneousMedium,
+    throughput: &mut Color,
+) -> Option<(Ray, HitInfo)> {
+    // 経路全体ではなくウォーク自体の重みで打ち切りを決める
+    let mut weight = Color::one();
+    for _ in 0..MAX_WALK_STEPS {
+        // 境界が無ければ閉じた形状の外に出ているので、何も無い空間を歩き続けない
+        let boundary = boundary_hit(world, &ray, &entry.material)?;
+        let sample = interior.sample_weighted(&ray, boundary.length, weight);
+        weight *= sample.weight;
+        let Some(t) = sample.scatter else {
+            *throughput *= weight;
+            return Some((ray, boundary));
+        };
+        let (scattered, _) = interior
+            .phase()
+            .sample(ray.direction.normalize(), (random(), random()));
+        ray = Ray::new(ray.at(t), scattered);
+
+        // 散乱のたびに吸収で暗くなるので、長いウォークは確率的に打ち切る
+        let survive = weight.max_element().min(1.0);
+        if random::<f64>() >= survive {
+            return None;
+        }
+        weight = weight / survive;
+    }
+    None
+}
+
+/// Nearest hit of `ray` on a surface with `material`, passing through other surfaces
+fn boundary_hit(
+    world: &(impl World + ?Sized),
+    ray: &Ray,
+    material: &Arc<dyn Material>,
+) -> Option<HitInfo> {
+    let mut t0 = EPS;
+    loop {
+        let hit = world.hit(ray, t0, f64::MAX)?;
+        if Arc::ptr_eq(&hit.material, material) {
+            return Some(hit);
+        }
+        t0 = hit.length;
+    }
+}
+
+/// Fraction of light passing along `ray` up to `t_max`, zero if a surface is in the way.
+/// Rays pass through the boundaries of media, starting in `medium`
+fn transmittance(
+    world: &(impl World + ?Sized),
+    mut ray: Ray,
+    mut t_max: f64,
+    mut medium: Option<Arc<dyn Medium>>,
+) -> Color {
+    let mut tr = Color::one();
+    loop {
+        let hit = world.hit(&ray, EPS, t_max);
+        if let Some(current) = &medium {
+            tr *= current.transmittance(&ray, hit.as_ref().map_or(t_max, |hit| hit.length));
+            if tr.max_element() <= 0.0 {
+                return tr;
+            }
+        }
+        let Some(hit) = hit else {
+            return tr;
+        };
+        if !hit.material.is_interface() {
+            return Color::zero();
+        }
+        if let Some(interface) = &hit.medium_interface {
+            medium = interface.towards(ray.direction, hit.direction);
+        }
+        t_max -= hit.length;
+        ray = Ray::new(hit.position, ray.direction);
+    }
+}
+
+/// Number of things light sampling chooses from, the lights and a sampled environment
+fn light_strategies(world: &(impl World + ?Sized)) -> usize {
+    world.lights().len() + usize::from(world.environment().is_sampled())
+}
+
+/// Multi-threaded tile based renderer
+pub struct Renderer {
+    /// Edge length of a square tile in pixels
+    pub tile_size: u32,
+    /// Number of worker threads
+    pub threads: usize,
+    /// Order in which tiles are rendered
+    pub tile_order: TileOrder,
+    /// Seed of every random decision, the same seed renders the same image
+    pub seed: u64,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            tile_size: TILE_SIZE,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            tile_order: TileOrder::default(),
+            seed: 0,
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
 
-    let mut img = RgbImage::new(scene.width(), scene.height());
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn tile_order(mut self, tile_order: TileOrder) -> Self {
+        self.tile_order = tile_order;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
 
-    img.enumerate_pixels_mut().for_each(|(x, y, pixel)| {
-        let u = x as f64 / (scene.width() - 1) as f64;
-        let v = (scene.height() - y - 1) as f64 / (scene.height() - 1) as f64;
+    pub fn render(&self, scene: &(impl Scene + Sync)) -> Film {
+        let camera = scene.camera();
+        let (width, height, spp) = (scene.width(), scene.height(), scene.spp().max(1));
+        let tiles = tile::tiles(width, height, self.tile_size, self.tile_order);
 
-        let ray = camera.ray(u, v);
+        let film = Mutex::new(Film::new(width, height));
+        let next_tile = AtomicUsize::new(0);
 
-        let rgb = scene.trace(ray).to_rgb();
+        thread::scope(|s| {
+            for _ in 0..self.threads.min(tiles.len()) {
+                s.spawn(|| {
+                    let mut sampler = scene.sampler().build(spp, self.seed);
+                    // 空いたスレッドから順に次のタイルを取りに行く
+                    while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        let pixels =
+                            self.render_tile(scene, camera.as_ref(), sampler.as_mut(), tile);
 
-        pixel[0] = rgb[0];
-        pixel[1] = rgb[1];
-        pixel[2] = rgb[2];
-    });
+                        let mut film = film.lock().unwrap();
+                        for (i, color) in pixels.into_iter().enumerate() {
+                            let x = tile.x + i as u32 % tile.width;
+                            let y = tile.y + i as u32 / tile.width;
+                            film.set(x, y, color);
+                        }
+                    }
+                });
+            }
+        });
+
+        film.into_inner().unwrap()
+    }
+
+    fn render_tile(
+        &self,
+        scene: &(impl Scene + Sync),
+        camera: &dyn Camera,
+        sampler: &mut dyn Sampler,
+        tile: &Tile,
+    ) -> Vec<Color> {
+        let (width, height, spp) = (scene.width(), scene.height(), scene.spp().max(1));
+        let mut pixels = Vec::with_capacity((tile.width * tile.height) as usize);
+
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                sampler.start_pixel(x, y);
+                // 材質などが使う乱数も画素ごとに初期化して、スレッド数によらず同じ画像にする
+                // (サンプラーと同じ系列にならないように末尾の値を変えてハッシュする)
+                rng::reseed(sampler::hash(&[self.seed, x as u64, y as u64, u64::MAX]));
+
+                let mut color = Color::zero();
+                for index in 0..spp {
+                    sampler.start_sample(index);
+                    // 画素内のランダムな位置を通るレイを飛ばす
+                    let (dx, dy) = sampler.get_2d();
+                    let u = (x as f64 + dx) / width as f64;
+                    let v = ((height - y - 1) as f64 + dy) / height as f64;
+
+                    // 被写界深度のためのレンズ上の位置
+                    let lens = sampler.get_2d();
+                    // 魚眼のイメージサークルの外などはレイが無く黒のまま
+                    if let Some(ray) = camera.ray(u, v, lens) {
+                        color += scene.trace(ray);
+                    }
+                }
+
+                pixels.push(color / spp as f64);
+            }
+        }
+
+        pixels
+    }
+}
 
-    img
+pub fn render(scene: impl Scene + Sync) -> Film {
+    Renderer::default().render(&scene)
 }
@@ -0,0 +1,27 @@
+use std::cell::RefCell;
+
+use rand::{
+    distributions::{Distribution, Standard},
+    rngs::SmallRng,
+    Rng, SeedableRng,
+};
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// Reseed the random number generator of the current thread.
+///
+/// The renderer reseeds it for every pixel so that images are reproducible
+/// regardless of the number of threads and the tile order.
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+/// Returns a random value from the generator of the current thread
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
@@ -0,0 +1,367 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+/// Generates the sample points of each pixel.
+///
+/// Each pixel is sampled `spp` times. A sample consumes as many dimensions as it needs
+/// through `get_1d` / `get_2d`; the first 2D sample is the sub-pixel position.
+pub trait Sampler: Send {
+    /// Prepare the samples of the pixel (x, y)
+    fn start_pixel(&mut self, x: u32, y: u32);
+    /// Prepare the `index`-th sample of the current pixel
+    fn start_sample(&mut self, index: u32);
+    /// Returns the next dimension of the current sample. [0..1)
+    fn get_1d(&mut self) -> f64;
+    /// Returns the next two dimensions of the current sample. [0..1)^2
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+    /// Independent uniform random samples
+    Random,
+    /// Jittered samples, one per stratum of a grid
+    #[default]
+    Stratified,
+    /// Halton sequence with a per-pixel random shift
+    Halton,
+    /// Sobol (0,2)-sequence with per-pixel scrambling
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Construct a sampler generating `spp` samples per pixel
+    pub fn build(self, spp: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Random => Box::new(RandomSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(spp, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+impl std::str::FromStr for SamplerKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "random" => Ok(SamplerKind::Random),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!(
+                "unknown sampler `{}`, expected one of random, stratified, halton, sobol",
+                s
+            )),
+        }
+    }
+}
+
+/// Converts the upper 53 bits of a hash to [0..1)
+pub fn to_unit(x: u64) -> f64 {
+    (x >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// Converts a 32-bit fixed point number to [0..1)
+fn u32_to_unit(x: u32) -> f64 {
+    x as f64 * (1.0 / (1u64 << 32) as f64)
+}
+
+/// SplitMix64 finalizer
+pub fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Hashes a list of integers to a well distributed 64-bit value
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |acc, v| {
+        mix(acc ^ v.wrapping_add(0x9e3779b97f4a7c15))
+    })
+}
+
+pub struct RandomSampler {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl RandomSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.rng = SmallRng::seed_from_u64(hash(&[self.seed, x as u64, y as u64]));
+    }
+
+    fn start_sample(&mut self, _index: u32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
+
+/// Per-pixel state shared by the sequence based samplers
+struct PixelState {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl PixelState {
+    const fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.pixel = hash(&[self.seed, x as u64, y as u64]);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    /// Returns a hash of the pixel and the next dimension, constant over the samples of a pixel
+    fn next_dimension(&mut self) -> (u64, u64) {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        (dimension, hash(&[self.pixel, dimension]))
+    }
+}
+
+/// Kensler's hashed permutation of `i` in [0..len)
+fn permute(mut i: u32, len: u32, p: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % len
+}
+
+pub struct StratifiedSampler {
+    state: PixelState,
+    nx: u32,
+    ny: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(spp: u32, seed: u64) -> Self {
+        // spp 以上の最小の格子を作る
+        let nx = (spp.max(1) as f64).sqrt().ceil() as u32;
+        let ny = spp.max(1).div_ceil(nx);
+        Self {
+            state: PixelState::new(seed),
+            nx,
+            ny,
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.state.start_pixel(x, y);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (_, h) = self.state.next_dimension();
+        let n = self.nx * self.ny;
+        let stratum = permute(self.state.index % n, n, h as u32);
+        let jitter = to_unit(hash(&[h, self.state.index as u64]));
+        (stratum as f64 + jitter) / n as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (_, h) = self.state.next_dimension();
+        // 画素内のサンプルの順番と格子の対応をシャッフルする
+        let stratum = permute(
+            self.state.index % (self.nx * self.ny),
+            self.nx * self.ny,
+            h as u32,
+        );
+        let jitter = hash(&[h, self.state.index as u64]);
+        let (sx, sy) = (stratum % self.nx, stratum / self.nx);
+        (
+            (sx as f64 + to_unit(jitter)) / self.nx as f64,
+            (sy as f64 + to_unit(mix(jitter))) / self.ny as f64,
+        )
+    }
+}
+
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Radical inverse of `index` in the given base
+fn radical_inverse(base: u64, mut index: u64) -> f64 {
+    let inv_base = (base as f64).recip();
+    let mut inv = inv_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * inv;
+        index /= base;
+        inv *= inv_base;
+    }
+    result
+}
+
+pub struct HaltonSampler {
+    state: PixelState,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: PixelState::new(seed),
+        }
+    }
+
+    fn sample(&mut self) -> f64 {
+        let (dimension, h) = self.state.next_dimension();
+        let value = if (dimension as usize) < PRIMES.len() {
+            radical_inverse(PRIMES[dimension as usize], self.state.index as u64)
+        } else {
+            // 素数表を超えた次元は乱数で埋める
+            to_unit(hash(&[h, self.state.index as u64]))
+        };
+        // Cranley-Patterson rotation で画素ごとに系列をずらす
+        (value + to_unit(h)).fract()
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.state.start_pixel(x, y);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.sample()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.sample(), self.sample())
+    }
+}
+
+/// First dimension of the Sobol sequence (van der Corput sequence)
+fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// Second dimension of the Sobol sequence
+fn sobol_1(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut v = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+/// Laine-Karras style hash, a permutation that only propagates bits upwards
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+/// Hash based Owen scrambling (Burley 2020)
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Each dimension pair is an independently Owen scrambled and shuffled (0,2)-sequence
+pub struct SobolSampler {
+    state: PixelState,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: PixelState::new(seed),
+        }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel(&mut self, x: u32, y: u32) {
+        self.state.start_pixel(x, y);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (_, h) = self.state.next_dimension();
+        let index = nested_uniform_scramble(self.state.index, h as u32);
+        u32_to_unit(nested_uniform_scramble(sobol_0(index), (h >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (_, h) = self.state.next_dimension();
+        // 次元ごとに添字をシャッフルして次元間の相関を消す
+        let index = nested_uniform_scramble(self.state.index, h as u32);
+        let seed = mix(h);
+        (
+            u32_to_unit(nested_uniform_scramble(sobol_0(index), seed as u32)),
+            u32_to_unit(nested_uniform_scramble(sobol_1(index), (seed >> 32) as u32)),
+        )
+    }
+}
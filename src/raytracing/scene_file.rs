@@ -0,0 +1,2155 @@
+//! Declarative scene description in TOML.
+//!
+//! ```toml
+//! [render]
+//! width = 640
+//! height = 360
+//! spp = 64
+//!
+//! [camera]
+//! origin = [0.0, 0.5, 2.0]
+//! lookat = [0.0, 0.0, -1.0]
+//! fov = 40.0
+//! aperture = 0.05
+//! autofocus = true
+//! # projection = "fisheye", "orthographic" or "equirectangular"
+//!
+//! [background]
+//! type = "gradient"
+//! bottom = [1.0, 1.0, 1.0]
+//! top = "#80b3ff"
+//! # or an HDR environment map lighting the scene:
+//! # type = "image", file = "sky.hdr", intensity = 1.0, rotation = 90.0
+//!
+//! [textures.checker]
+//! type = "checker"
+//! even = [0.2, 0.3, 0.1]
+//! odd = 0.9
+//! scale = 2.0
+//! # also "image" (file, gamma, wrap, filter, scale), "noise", "worley", "marble" and "wood"
+//!
+//! [materials.ground]
+//! type = "lambertian"
+//! albedo = { texture = "checker" }
+//!
+//! [materials.wax]
+//! type = "subsurface"
+//! albedo = [0.95, 0.85, 0.7]
+//! mean_free_path = [0.2, 0.1, 0.05]
+//! # ior = 1.4, roughness = 0.3 and g = 0.0 by default, the shapes must be closed
+//!
+//! [[shapes]]
+//! type = "plane"
+//! point = [0.0, -0.5, 0.0]
+//! normal = [0.0, 1.0, 0.0]
+//! material = "ground"
+//! # also "sphere", "disk", "quad", "box", "cylinder", "cone", "torus", "triangle", "mesh" and
+//! # "sdf" (distance fields, see `scenes/sdf.toml`)
+//!
+//! [[shapes]]
+//! type = "csg"
+//! operation = "difference"
+//! shapes = [
+//!     { type = "box", min = [-0.5, 0.0, -0.5], max = [0.5, 1.0, 0.5], material = "ground" },
+//!     { type = "sphere", center = [0.0, 1.0, 0.0], radius = 0.6, material = "ground" },
+//! ]
+//!
+//! [[shapes]]
+//! type = "obj"
+//! file = "models/teapot.obj"
+//!
+//! [prototypes.chair]
+//! type = "obj"
+//! file = "models/chair.obj"
+//!
+//! [[shapes]]
+//! type = "instance"
+//! prototype = "chair"
+//! translate = [1.0, 0.0, -2.0]
+//! rotate = [0.0, 90.0, 0.0]
+//! scale = 0.5
+//!
+//! [media.fog]
+//! type = "homogeneous"
+//! absorption = 0.01
+//! scattering = [0.1, 0.1, 0.12]
+//! g = 0.3
+//! # or a density grid, from raw 8-bit or float voxels or generated noise:
+//! # type = "grid", file = "smoke.raw", resolution = [64, 64, 64], min = .., max = ..,
+//! # extinction = 10.0, albedo = 0.9
+//! # `medium = "fog"` in [camera] fills the scene with it
+//!
+//! [[shapes]]
+//! type = "volume"
+//! inside = "fog"
+//! shape = { type = "sphere", center = [0.0, 1.0, 0.0], radius = 1.0, material = "boundary" }
+//! # a material of type "interface" lets rays through, others such as "dielectric" refract
+//!
+//! [[lights]]
+//! type = "rect"
+//! corner = [-0.5, 2.0, -1.5]
+//! edge_u = [1.0, 0.0, 0.0]
+//! edge_v = [0.0, 0.0, 1.0]
+//! intensity = 10.0
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::Deserialize;
+
+use super::{
+    aabb::Aabb,
+    bvh::Bvh,
+    camera::{
+        Aperture, Camera, EquirectangularCamera, FisheyeCamera, FisheyeMapping, OrthographicCamera,
+        PerspectiveCamera,
+    },
+    csg::{Csg, CsgOperation},
+    environment::{ConstantEnvironment, Environment, GradientEnvironment, ImageEnvironment},
+    hit_info::HitInfo,
+    light::{DirectionalLight, Light, MeshLight, PointLight, RectLight, SphereLight, SpotLight},
+    material::{Dielectric, DiffuseLight, InterfaceMaterial, Lambertian, Material, Metal},
+    medium::{DensityGrid, GridMedium, HomogeneousMedium, Medium, MediumInterface, Volume},
+    mesh::{Mesh, TriangleMesh},
+    microfacet::conductors,
+    obj::{load_obj, ObjError},
+    primitives::{Cone, Cuboid, Cylinder, Disk, Plane, Quad, Torus},
+    principled::Principled,
+    quaternion::Quaternion,
+    ray::Ray,
+    render::{PathTracer, RenderSettings, Scene, World},
+    sampler::SamplerKind,
+    sdf::{
+        BoxSdf, CapsuleSdf, CylinderSdf, DistanceField, Mandelbulb, Repeat, Sdf, SmoothUnion,
+        SphereSdf, TorusSdf, Twist,
+    },
+    shapes::{Shape, Sphere, Triangle},
+    subsurface::Subsurface,
+    texture::{
+        CheckerTexture, Filter, ImageTexture, MarbleTexture, NoiseTexture, SolidColor, Texture,
+        TextureSpace, WoodTexture, WorleyFeature, WorleyTexture, WrapMode,
+    },
+    transform::{Instance, Transform},
+    Color, Float3,
+};
+
+#[derive(Debug)]
+pub enum SceneErrorKind {
+    Io(std::io::Error),
+    /// The file is not valid TOML or does not match the scene schema
+    Parse(Box<toml::de::Error>),
+    /// A value is well-formed but invalid, `key` is the dotted path to it
+    Invalid {
+        key: String,
+        message: String,
+    },
+    /// A referenced OBJ file failed to load
+    Obj {
+        key: String,
+        error: Box<ObjError>,
+    },
+    /// A referenced image failed to load
+    Image {
+        key: String,
+        error: Box<image::ImageError>,
+    },
+    /// A referenced voxel file failed to load
+    Voxels {
+        key: String,
+        error: std::io::Error,
+    },
+}
+
+/// Error raised while loading a scene file
+#[derive(Debug)]
+pub struct SceneError {
+    pub file: PathBuf,
+    pub kind: SceneErrorKind,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            SceneErrorKind::Io(e) => write!(f, "{}: {}", self.file.display(), e),
+            SceneErrorKind::Parse(e) => write!(f, "{}: {}", self.file.display(), e),
+            SceneErrorKind::Invalid { key, message } => {
+                write!(f, "{}: `{}`: {}", self.file.display(), key, message)
+            }
+            SceneErrorKind::Obj { key, error } => {
+                write!(f, "{}: `{}`: {}", self.file.display(), key, error)
+            }
+            SceneErrorKind::Image { key, error } => {
+                write!(f, "{}: `{}`: {}", self.file.display(), key, error)
+            }
+            SceneErrorKind::Voxels { key, error } => {
+                write!(f, "{}: `{}`: {}", self.file.display(), key, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            SceneErrorKind::Io(e) => Some(e),
+            SceneErrorKind::Parse(e) => Some(e.as_ref()),
+            SceneErrorKind::Obj { error, .. } => Some(error.as_ref()),
+            SceneErrorKind::Image { error, .. } => Some(error.as_ref()),
+            SceneErrorKind::Voxels { error, .. } => Some(error),
+            SceneErrorKind::Invalid { .. } => None,
+        }
+    }
+}
+
+/// Color written as `[r, g, b]`, a single gray level or a hex string such as `"#ff8800"`.
+///
+/// Where textures are allowed, `{ texture = "name" }` refers to one of `[textures]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Rgb([f64; 3]),
+    Gray(f64),
+    Hex(String),
+    Texture { texture: String },
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RenderSection {
+    width: Option<u32>,
+    height: Option<u32>,
+    spp: Option<u32>,
+    sampler: Option<String>,
+    max_depth: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraSection {
+    origin: [f64; 3],
+    lookat: [f64; 3],
+    #[serde(default = "default_up")]
+    up: [f64; 3],
+    #[serde(default)]
+    projection: Projection,
+    /// Vertical field of view, the diameter of the image circle for fisheye
+    #[serde(default = "default_fov")]
+    fov: f64,
+    /// Visible height in world units for the orthographic projection
+    #[serde(default = "default_view_height")]
+    view_height: f64,
+    #[serde(default)]
+    mapping: FisheyeMapping,
+    /// Lens radius, zero renders everything in focus
+    #[serde(default)]
+    aperture: f64,
+    /// Defaults to the distance to `lookat`
+    focus_distance: Option<f64>,
+    /// Focus on whatever is at the center of the image
+    #[serde(default)]
+    autofocus: bool,
+    /// Number of diaphragm blades, a circular aperture when omitted
+    blades: Option<u32>,
+    /// Rotation of the blades in degrees
+    #[serde(default)]
+    blade_rotation: f64,
+    /// Medium the camera is in, from `[media]`
+    medium: Option<String>,
+}
+
+fn default_up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_fov() -> f64 {
+    60.0
+}
+
+fn default_view_height() -> f64 {
+    2.0
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Projection {
+    #[default]
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundSection {
+    Constant {
+        color: ColorValue,
+    },
+    Gradient {
+        bottom: ColorValue,
+        top: ColorValue,
+    },
+    /// Latitude-longitude HDR image
+    Image {
+        file: PathBuf,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+        /// Rotation around the up axis in degrees
+        #[serde(default)]
+        rotation: f64,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureSection {
+    Image {
+        file: PathBuf,
+        /// Gamma the pixels are decoded with, 1 for data such as roughness maps
+        #[serde(default = "default_gamma")]
+        gamma: f64,
+        #[serde(default)]
+        wrap: Wrap,
+        #[serde(default)]
+        filter: ImageFilter,
+        /// Repetitions of the image along u and v
+        #[serde(default = "default_uv_scale")]
+        scale: [f64; 2],
+    },
+    Checker {
+        even: ColorValue,
+        odd: ColorValue,
+        /// Cells per unit
+        #[serde(default = "default_texture_scale")]
+        scale: f64,
+        #[serde(default)]
+        space: Space,
+    },
+    Noise {
+        #[serde(default = "default_texture_scale")]
+        scale: f64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        /// Colors at the lowest and the highest noise value
+        colors: Option<[ColorValue; 2]>,
+        #[serde(default)]
+        seed: u64,
+    },
+    Worley {
+        #[serde(default = "default_texture_scale")]
+        scale: f64,
+        #[serde(default)]
+        feature: Feature,
+        colors: Option<[ColorValue; 2]>,
+        #[serde(default)]
+        seed: u64,
+    },
+    Marble {
+        #[serde(default = "default_texture_scale")]
+        scale: f64,
+        turbulence: Option<f64>,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        /// Colors of the veins and of the stone between them
+        colors: Option<[ColorValue; 2]>,
+        #[serde(default)]
+        seed: u64,
+    },
+    Wood {
+        /// Rings per unit
+        #[serde(default = "default_texture_scale")]
+        scale: f64,
+        turbulence: Option<f64>,
+        /// Colors of the dark rings and of the wood between them
+        colors: Option<[ColorValue; 2]>,
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+fn default_gamma() -> f64 {
+    2.2
+}
+
+fn default_uv_scale() -> [f64; 2] {
+    [1.0, 1.0]
+}
+
+fn default_texture_scale() -> f64 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    6
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Wrap {
+    #[default]
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ImageFilter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Space {
+    #[default]
+    World,
+    Uv,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Feature {
+    #[default]
+    F1,
+    Edge,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialSection {
+    Lambertian {
+        albedo: ColorValue,
+    },
+    Metal {
+        albedo: ColorValue,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        ior: f64,
+    },
+    DiffuseLight {
+        emit: ColorValue,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    Principled(PrincipledSection),
+    /// Translucent material lit by random walks through its inside
+    Subsurface {
+        albedo: ColorValue,
+        /// Distance between scattering events inside, per color channel
+        mean_free_path: ColorValue,
+        #[serde(default = "default_subsurface_ior")]
+        ior: f64,
+        #[serde(default = "default_subsurface_roughness")]
+        roughness: f64,
+        #[serde(default)]
+        g: f64,
+    },
+    /// Invisible boundary of a medium
+    Interface,
+}
+
+fn default_subsurface_ior() -> f64 {
+    1.4
+}
+
+fn default_subsurface_roughness() -> f64 {
+    0.3
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+/// Parameters of `Principled`, omitted ones take its defaults
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PrincipledSection {
+    base_color: ColorValue,
+    /// Defaults to 1 when a complex IOR is given and 0 otherwise
+    metallic: Option<f64>,
+    roughness: f64,
+    anisotropic: f64,
+    anisotropic_rotation: f64,
+    specular: f64,
+    specular_tint: f64,
+    sheen: f64,
+    sheen_tint: f64,
+    clearcoat: f64,
+    clearcoat_roughness: f64,
+    /// Name of a metal such as `"gold"` whose complex IOR replaces the base color
+    conductor: Option<String>,
+    /// Complex IOR given explicitly, needs both `eta` and `k`
+    eta: Option<[f64; 3]>,
+    k: Option<[f64; 3]>,
+}
+
+impl Default for PrincipledSection {
+    fn default() -> Self {
+        let defaults = Principled::default();
+        Self {
+            base_color: ColorValue::Rgb(defaults.base_color.to_array()),
+            metallic: None,
+            roughness: defaults.roughness,
+            anisotropic: defaults.anisotropic,
+            anisotropic_rotation: defaults.anisotropic_rotation,
+            specular: defaults.specular,
+            specular_tint: defaults.specular_tint,
+            sheen: defaults.sheen,
+            sheen_tint: defaults.sheen_tint,
+            clearcoat: defaults.clearcoat,
+            clearcoat_roughness: defaults.clearcoat_roughness,
+            conductor: None,
+            eta: None,
+            k: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeSection {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        material: String,
+    },
+    Plane {
+        point: [f64; 3],
+        #[serde(default = "default_up")]
+        normal: [f64; 3],
+        material: String,
+    },
+    Disk {
+        center: [f64; 3],
+        #[serde(default = "default_up")]
+        normal: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Quad {
+        corner: [f64; 3],
+        edge_u: [f64; 3],
+        edge_v: [f64; 3],
+        material: String,
+    },
+    /// Box between two corners, optionally rotated around its center
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        /// Rotation around the x, y and z axes in degrees, applied in that order
+        #[serde(default)]
+        rotate: [f64; 3],
+        material: String,
+    },
+    /// Cylinder standing on the center of its base
+    Cylinder {
+        base: [f64; 3],
+        #[serde(default = "default_up")]
+        axis: [f64; 3],
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: String,
+    },
+    /// Cone standing on the center of its base
+    Cone {
+        base: [f64; 3],
+        #[serde(default = "default_up")]
+        axis: [f64; 3],
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: String,
+    },
+    Torus {
+        center: [f64; 3],
+        #[serde(default = "default_up")]
+        axis: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    Mesh {
+        positions: Vec<[f64; 3]>,
+        triangles: Vec<[usize; 3]>,
+        #[serde(default)]
+        normals: Vec<[f64; 3]>,
+        #[serde(default)]
+        uvs: Vec<[f64; 2]>,
+        material: String,
+    },
+    Obj {
+        file: PathBuf,
+        /// Overrides the materials of the MTL file
+        material: Option<String>,
+    },
+    /// Distance field rendered by sphere tracing
+    Sdf {
+        field: FieldSection,
+        /// Corners of the box to search in, needed for fields that repeat forever
+        bounds: Option<[[f64; 3]; 2]>,
+        /// Factor shortening the steps, for twisted fields
+        #[serde(default = "default_step_scale")]
+        step_scale: f64,
+        #[serde(default = "default_max_steps")]
+        max_steps: u32,
+        material: String,
+    },
+    /// Binds media from `[media]` to the inside and outside of a shape
+    Volume {
+        shape: Box<ShapeSection>,
+        inside: Option<String>,
+        outside: Option<String>,
+    },
+    /// Solids combined in order, `difference` cuts the later shapes out of the first
+    Csg {
+        operation: Operation,
+        shapes: Vec<ShapeSection>,
+    },
+    /// Copy of a shape from `[prototypes]`, scaled, then rotated, then translated
+    Instance {
+        prototype: String,
+        #[serde(default)]
+        translate: [f64; 3],
+        /// Rotation around the x, y and z axes in degrees, applied in that order
+        #[serde(default)]
+        rotate: [f64; 3],
+        #[serde(default = "default_scale")]
+        scale: ScaleValue,
+        /// Overrides the materials of the prototype
+        material: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+fn default_capped() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum FieldSection {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+    },
+    Box {
+        center: [f64; 3],
+        size: [f64; 3],
+        #[serde(default)]
+        rounding: f64,
+    },
+    /// Torus around the y axis
+    Torus {
+        center: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Capsule {
+        a: [f64; 3],
+        b: [f64; 3],
+        radius: f64,
+    },
+    /// Cylinder along the y axis
+    Cylinder {
+        center: [f64; 3],
+        radius: f64,
+        height: f64,
+    },
+    SmoothUnion {
+        fields: Vec<FieldSection>,
+        smoothness: f64,
+    },
+    /// Twist around the y axis
+    Twist {
+        field: Box<FieldSection>,
+        /// Degrees per unit of height
+        rate: f64,
+    },
+    Repeat {
+        field: Box<FieldSection>,
+        /// Period along each axis, 0 to not repeat
+        period: [f64; 3],
+    },
+    Mandelbulb {
+        center: [f64; 3],
+        #[serde(default = "default_fractal_scale")]
+        scale: f64,
+        #[serde(default = "default_power")]
+        power: f64,
+        #[serde(default = "default_iterations")]
+        iterations: u32,
+    },
+}
+
+fn default_step_scale() -> f64 {
+    1.0
+}
+
+fn default_max_steps() -> u32 {
+    256
+}
+
+fn default_fractal_scale() -> f64 {
+    1.0
+}
+
+fn default_power() -> f64 {
+    8.0
+}
+
+fn default_iterations() -> u32 {
+    12
+}
+
+/// Scale written as a single factor or one factor per axis
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ScaleValue {
+    Uniform(f64),
+    Axes([f64; 3]),
+}
+
+fn default_scale() -> ScaleValue {
+    ScaleValue::Uniform(1.0)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightSection {
+    Point {
+        position: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: ColorValue,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    Spot {
+        position: [f64; 3],
+        lookat: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: ColorValue,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+        /// Half angle of the cone in degrees
+        #[serde(default = "default_spot_angle")]
+        angle: f64,
+        /// Fraction of the cone used for the soft edge
+        #[serde(default = "default_spot_blend")]
+        blend: f64,
+    },
+    Directional {
+        /// Direction the light travels in
+        direction: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: ColorValue,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        #[serde(default = "default_light_color")]
+        color: ColorValue,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    Rect {
+        corner: [f64; 3],
+        edge_u: [f64; 3],
+        edge_v: [f64; 3],
+        #[serde(default = "default_light_color")]
+        color: ColorValue,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    Mesh {
+        positions: Vec<[f64; 3]>,
+        triangles: Vec<[usize; 3]>,
+        #[serde(default = "default_light_color")]
+        color: ColorValue,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MediumSection {
+    /// Coefficients per unit of length
+    Homogeneous {
+        #[serde(default = "default_absorption")]
+        absorption: ColorValue,
+        scattering: ColorValue,
+        /// Henyey-Greenstein asymmetry, positive scatters forward
+        #[serde(default)]
+        g: f64,
+    },
+    /// Densities on a voxel grid filling the box from `min` to `max`
+    Grid {
+        /// Raw voxel file, one byte or one little-endian float per voxel with x fastest
+        file: Option<PathBuf>,
+        /// Fractal noise cloud instead of a file
+        noise: Option<NoiseGrid>,
+        resolution: [usize; 3],
+        min: [f64; 3],
+        max: [f64; 3],
+        /// Extinction per unit of length where the density is 1
+        extinction: f64,
+        #[serde(default = "default_medium_albedo")]
+        albedo: ColorValue,
+        #[serde(default)]
+        g: f64,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoiseGrid {
+    #[serde(default = "default_noise_scale")]
+    scale: f64,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+    #[serde(default)]
+    seed: u64,
+}
+
+fn default_absorption() -> ColorValue {
+    ColorValue::Gray(0.0)
+}
+
+fn default_medium_albedo() -> ColorValue {
+    ColorValue::Gray(1.0)
+}
+
+fn default_noise_scale() -> f64 {
+    4.0
+}
+
+fn default_light_color() -> ColorValue {
+    ColorValue::Gray(1.0)
+}
+
+fn default_spot_angle() -> f64 {
+    30.0
+}
+
+fn default_spot_blend() -> f64 {
+    0.1
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    render: RenderSection,
+    camera: CameraSection,
+    background: Option<BackgroundSection>,
+    #[serde(default)]
+    textures: HashMap<String, TextureSection>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialSection>,
+    #[serde(default)]
+    media: HashMap<String, MediumSection>,
+    /// Shapes placed in the scene only through instances
+    #[serde(default)]
+    prototypes: HashMap<String, ShapeSection>,
+    #[serde(default)]
+    shapes: Vec<ShapeSection>,
+    #[serde(default)]
+    lights: Vec<LightSection>,
+}
+
+/// Scene loaded from a scene description file
+pub struct FileScene {
+    pub settings: RenderSettings,
+    camera: CameraSection,
+    environment: Box<dyn Environment>,
+    objects: Bvh,
+    lights: Vec<Box<dyn Light>>,
+    /// Numbers of the named and the OBJ materials, in name order
+    material_ids: HashMap<usize, usize>,
+    medium: Option<Arc<dyn Medium>>,
+}
+
+impl FileScene {
+    /// Load a scene file, relative paths inside it are resolved against its directory
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|e| SceneError {
+            file: path.to_path_buf(),
+            kind: SceneErrorKind::Io(e),
+        })?;
+        Self::parse(&source, path)
+    }
+
+    /// Parse a scene description, `path` names the file in errors and anchors relative paths
+    pub fn parse(source: &str, path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let error = |kind| SceneError {
+            file: path.to_path_buf(),
+            kind,
+        };
+        let file: SceneFile =
+            toml::from_str(source).map_err(|e| error(SceneErrorKind::Parse(Box::new(e))))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::build(file, dir).map_err(error)
+    }
+
+    fn build(file: SceneFile, dir: &Path) -> Result<Self, SceneErrorKind> {
+        let defaults = RenderSettings::default();
+        let sampler = match &file.render.sampler {
+            Some(name) => name
+                .parse::<SamplerKind>()
+                .map_err(|message| invalid("render.sampler", message))?,
+            None => defaults.sampler,
+        };
+        let settings = RenderSettings {
+            width: file.render.width.unwrap_or(defaults.width),
+            height: file.render.height.unwrap_or(defaults.height),
+            spp: file.render.spp.unwrap_or(defaults.spp),
+            sampler,
+            max_depth: file.render.max_depth.unwrap_or(defaults.max_depth),
+        };
+        if settings.width == 0 || settings.height == 0 {
+            return Err(invalid("render", "width and height must be positive"));
+        }
+
+        let camera = &file.camera;
+        if camera.fov <= 0.0 {
+            return Err(invalid("camera.fov", "must be positive"));
+        }
+        if matches!(camera.projection, Projection::Perspective) && camera.fov >= 180.0 {
+            return Err(invalid(
+                "camera.fov",
+                "must be less than 180 for the perspective projection",
+            ));
+        }
+        if camera.view_height <= 0.0 {
+            return Err(invalid("camera.view_height", "must be positive"));
+        }
+        if camera.aperture < 0.0 {
+            return Err(invalid("camera.aperture", "must not be negative"));
+        }
+        if matches!(camera.focus_distance, Some(d) if d <= 0.0) {
+            return Err(invalid("camera.focus_distance", "must be positive"));
+        }
+        if matches!(camera.blades, Some(n) if n < 3) {
+            return Err(invalid("camera.blades", "needs at least 3 blades"));
+        }
+        if !matches!(camera.projection, Projection::Perspective) {
+            // レンズの設定は透視投影でしか使われない
+            let lens_keys = [
+                ("camera.aperture", camera.aperture != 0.0),
+                ("camera.focus_distance", camera.focus_distance.is_some()),
+                ("camera.autofocus", camera.autofocus),
+                ("camera.blades", camera.blades.is_some()),
+                ("camera.blade_rotation", camera.blade_rotation != 0.0),
+            ];
+            if let Some((key, _)) = lens_keys.into_iter().find(|&(_, set)| set) {
+                return Err(invalid(key, "only supported by the perspective projection"));
+            }
+        }
+        if camera.autofocus && camera.aperture == 0.0 {
+            return Err(invalid("camera.autofocus", "needs a positive aperture"));
+        }
+        let view = to_float3(&camera.lookat) - to_float3(&camera.origin);
+        let up = to_float3(&camera.up);
+        if view.near_zero() {
+            return Err(invalid("camera.lookat", "must differ from the origin"));
+        }
+        if up.near_zero() {
+            return Err(invalid("camera.up", "must not be zero"));
+        }
+        // 真上や真下を見るときは既定の up では向きが決まらない
+        if view.normalize().cross(up.normalize()).near_zero() {
+            return Err(invalid(
+                "camera.up",
+                "must not be parallel to the view direction",
+            ));
+        }
+
+        let environment: Box<dyn Environment> = match &file.background {
+            None => Box::new(GradientEnvironment::new(
+                Color::one(),
+                Color::new(0.5, 0.7, 1.0),
+            )),
+            Some(BackgroundSection::Constant { color }) => Box::new(ConstantEnvironment::new(
+                to_color(color, "background.color")?,
+            )),
+            Some(BackgroundSection::Gradient { bottom, top }) => {
+                Box::new(GradientEnvironment::new(
+                    to_color(bottom, "background.bottom")?,
+                    to_color(top, "background.top")?,
+                ))
+            }
+            Some(BackgroundSection::Image {
+                file,
+                intensity,
+                rotation,
+            }) => {
+                let image = ImageEnvironment::load(dir.join(file)).map_err(|error| {
+                    SceneErrorKind::Image {
+                        key: "background.file".to_string(),
+                        error: Box::new(error),
+                    }
+                })?;
+                Box::new(
+                    image
+                        .with_intensity(*intensity)
+                        .with_rotation(Quaternion::from_rot_y(rotation.to_radians())),
+                )
+            }
+        };
+
+        let mut textures: HashMap<&str, Arc<dyn Texture>> = HashMap::new();
+        for (name, section) in &file.textures {
+            let key = format!("textures.{}", name);
+            textures.insert(name, build_texture(section, &key, dir)?);
+        }
+
+        let mut materials: HashMap<&str, Arc<dyn Material>> = HashMap::new();
+        for (name, section) in &file.materials {
+            let key = format!("materials.{}", name);
+            materials.insert(name, build_material(section, &key, &textures)?);
+        }
+        // 名前順に番号を振り、OBJ の材質はその後ろに続ける
+        let mut material_ids = HashMap::new();
+        let mut names: Vec<_> = materials.keys().collect();
+        names.sort();
+        for name in names {
+            let id = material_ids.len();
+            material_ids
+                .entry(material_key(&materials[name]))
+                .or_insert(id);
+        }
+        let mut media: HashMap<&str, Arc<dyn Medium>> = HashMap::new();
+        for (name, section) in &file.media {
+            let key = format!("media.{}", name);
+            media.insert(name, build_medium(section, &key, dir)?);
+        }
+        let medium = file
+            .camera
+            .medium
+            .as_ref()
+            .map(|name| find_medium(&media, name, "camera.medium".to_string()))
+            .transpose()?;
+
+        let mut shapes = ShapeBuilder {
+            dir,
+            materials,
+            material_ids,
+            media,
+            prototypes: HashMap::new(),
+        };
+        let mut names: Vec<_> = file.prototypes.keys().collect();
+        names.sort();
+        for name in names {
+            let key = |field: &str| format!("prototypes.{}.{}", name, field);
+            let section = &file.prototypes[name];
+            if matches!(section, ShapeSection::Instance { .. }) {
+                return Err(invalid(key("type"), "a prototype cannot be an instance"));
+            }
+            let mut parts = shapes.build(section, &key)?;
+            let prototype: Arc<dyn Shape> = if parts.len() == 1 {
+                Arc::from(parts.remove(0))
+            } else {
+                Arc::new(Bvh::new(parts))
+            };
+            shapes.prototypes.insert(name, prototype);
+        }
+
+        let mut objects: Vec<Box<dyn Shape>> = Vec::new();
+        for (i, section) in file.shapes.iter().enumerate() {
+            let key = |field: &str| format!("shapes[{}].{}", i, field);
+            objects.extend(shapes.build(section, &key)?);
+        }
+        let material_ids = shapes.material_ids;
+
+        let mut lights: Vec<Box<dyn Light>> = Vec::new();
+        for (i, section) in file.lights.iter().enumerate() {
+            let key = |field: &str| format!("lights[{}].{}", i, field);
+            let emission = |color: &ColorValue, intensity: f64| {
+                Ok::<_, SceneErrorKind>(to_color(color, &key("color"))? * intensity)
+            };
+            match section {
+                LightSection::Point {
+                    position,
+                    color,
+                    intensity,
+                } => lights.push(Box::new(PointLight::new(
+                    to_float3(position),
+                    emission(color, *intensity)?,
+                ))),
+                LightSection::Spot {
+                    position,
+                    lookat,
+                    color,
+                    intensity,
+                    angle,
+                    blend,
+                } => {
+                    let (position, lookat) = (to_float3(position), to_float3(lookat));
+                    if (lookat - position).near_zero() {
+                        return Err(invalid(key("lookat"), "must differ from the position"));
+                    }
+                    lights.push(Box::new(SpotLight::new(
+                        position,
+                        lookat - position,
+                        emission(color, *intensity)?,
+                        *angle,
+                        *blend,
+                    )));
+                }
+                LightSection::Directional {
+                    direction,
+                    color,
+                    intensity,
+                } => {
+                    let direction = to_float3(direction);
+                    if direction.near_zero() {
+                        return Err(invalid(key("direction"), "must not be zero"));
+                    }
+                    lights.push(Box::new(DirectionalLight::new(
+                        direction,
+                        emission(color, *intensity)?,
+                    )));
+                }
+                LightSection::Sphere {
+                    center,
+                    radius,
+                    color,
+                    intensity,
+                } => {
+                    if *radius <= 0.0 {
+                        return Err(invalid(key("radius"), "must be positive"));
+                    }
+                    lights.push(Box::new(SphereLight::new(
+                        to_float3(center),
+                        *radius,
+                        emission(color, *intensity)?,
+                    )));
+                }
+                LightSection::Rect {
+                    corner,
+                    edge_u,
+                    edge_v,
+                    color,
+                    intensity,
+                } => {
+                    let (edge_u, edge_v) = (to_float3(edge_u), to_float3(edge_v));
+                    if edge_u.cross(edge_v).near_zero() {
+                        return Err(invalid(key("edge_v"), "edges must span an area"));
+                    }
+                    lights.push(Box::new(RectLight::new(
+                        to_float3(corner),
+                        edge_u,
+                        edge_v,
+                        emission(color, *intensity)?,
+                    )));
+                }
+                LightSection::Mesh {
+                    positions,
+                    triangles,
+                    color,
+                    intensity,
+                } => {
+                    check_triangles(triangles, positions.len(), &key("triangles"))?;
+                    let mesh = Mesh::new(
+                        positions.iter().map(to_float3).collect(),
+                        triangles.clone(),
+                        Arc::new(DiffuseLight::new(emission(color, *intensity)?)),
+                    );
+                    lights.push(Box::new(MeshLight::new(mesh)));
+                }
+            }
+        }
+        // 面積を持つ光源は、光線が当たるよう形状もシーンに加える
+        for (light_id, light) in lights.iter().enumerate() {
+            objects.extend(light.shape(light_id));
+        }
+
+        Ok(Self {
+            settings,
+            camera: file.camera,
+            environment,
+            objects: Bvh::new(objects),
+            lights,
+            material_ids,
+            medium,
+        })
+    }
+}
+
+/// Builds shapes, resolving the materials and prototypes they refer to
+struct ShapeBuilder<'a> {
+    dir: &'a Path,
+    materials: HashMap<&'a str, Arc<dyn Material>>,
+    material_ids: HashMap<usize, usize>,
+    media: HashMap<&'a str, Arc<dyn Medium>>,
+    prototypes: HashMap<&'a str, Arc<dyn Shape>>,
+}
+
+impl ShapeBuilder<'_> {
+    fn material(&self, name: &str, key: String) -> Result<Arc<dyn Material>, SceneErrorKind> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| invalid(key, format!("unknown material `{}`", name)))
+    }
+
+    /// Build the shapes of a section, an OBJ file gives one per mesh
+    fn build(
+        &mut self,
+        section: &ShapeSection,
+        key: &dyn Fn(&str) -> String,
+    ) -> Result<Vec<Box<dyn Shape>>, SceneErrorKind> {
+        let mut parts: Vec<Box<dyn Shape>> = Vec::new();
+        match section {
+            ShapeSection::Sphere {
+                center,
+                radius,
+                material: name,
+            } => {
+                check_positive(*radius, &key("radius"))?;
+                parts.push(Box::new(Sphere::new(
+                    to_float3(center),
+                    *radius,
+                    self.material(name, key("material"))?,
+                )));
+            }
+            ShapeSection::Triangle {
+                vertices: [v0, v1, v2],
+                material: name,
+            } => parts.push(Box::new(Triangle::new(
+                to_float3(v0),
+                to_float3(v1),
+                to_float3(v2),
+                self.material(name, key("material"))?,
+            ))),
+            ShapeSection::Plane {
+                point,
+                normal,
+                material: name,
+            } => parts.push(Box::new(Plane::new(
+                to_float3(point),
+                to_direction(normal, &key("normal"))?,
+                self.material(name, key("material"))?,
+            ))),
+            ShapeSection::Disk {
+                center,
+                normal,
+                radius,
+                material: name,
+            } => {
+                check_positive(*radius, &key("radius"))?;
+                parts.push(Box::new(Disk::new(
+                    to_float3(center),
+                    to_direction(normal, &key("normal"))?,
+                    *radius,
+                    self.material(name, key("material"))?,
+                )));
+            }
+            ShapeSection::Quad {
+                corner,
+                edge_u,
+                edge_v,
+                material: name,
+            } => {
+                if to_float3(edge_u).cross(to_float3(edge_v)).near_zero() {
+                    return Err(invalid(key("edge_v"), "must not be parallel to `edge_u`"));
+                }
+                parts.push(Box::new(Quad::new(
+                    to_float3(corner),
+                    to_float3(edge_u),
+                    to_float3(edge_v),
+                    self.material(name, key("material"))?,
+                )));
+            }
+            ShapeSection::Box {
+                min,
+                max,
+                rotate,
+                material: name,
+            } => {
+                if (0..3).any(|i| min[i] >= max[i]) {
+                    return Err(invalid(
+                        key("max"),
+                        "must be greater than `min` on every axis",
+                    ));
+                }
+                let (min, max) = (to_float3(min), to_float3(max));
+                parts.push(Box::new(Cuboid::oriented(
+                    (min + max) * 0.5,
+                    max - min,
+                    to_rotation(rotate),
+                    self.material(name, key("material"))?,
+                )));
+            }
+            ShapeSection::Cylinder {
+                base,
+                axis,
+                radius,
+                height,
+                capped,
+                material: name,
+            } => {
+                check_positive(*radius, &key("radius"))?;
+                check_positive(*height, &key("height"))?;
+                let cylinder = Cylinder::new(
+                    to_float3(base),
+                    to_direction(axis, &key("axis"))?,
+                    *radius,
+                    *height,
+                    self.material(name, key("material"))?,
+                );
+                parts.push(if *capped {
+                    Box::new(cylinder)
+                } else {
+                    Box::new(cylinder.open())
+                });
+            }
+            ShapeSection::Cone {
+                base,
+                axis,
+                radius,
+                height,
+                capped,
+                material: name,
+            } => {
+                check_positive(*radius, &key("radius"))?;
+                check_positive(*height, &key("height"))?;
+                let cone = Cone::new(
+                    to_float3(base),
+                    to_direction(axis, &key("axis"))?,
+                    *radius,
+                    *height,
+                    self.material(name, key("material"))?,
+                );
+                parts.push(if *capped {
+                    Box::new(cone)
+                } else {
+                    Box::new(cone.open())
+                });
+            }
+            ShapeSection::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+                material: name,
+            } => {
+                check_positive(*major_radius, &key("major_radius"))?;
+                check_positive(*minor_radius, &key("minor_radius"))?;
+                parts.push(Box::new(Torus::new(
+                    to_float3(center),
+                    to_direction(axis, &key("axis"))?,
+                    *major_radius,
+                    *minor_radius,
+                    self.material(name, key("material"))?,
+                )));
+            }
+            ShapeSection::Mesh {
+                positions,
+                triangles,
+                normals,
+                uvs,
+                material: name,
+            } => {
+                check_triangles(triangles, positions.len(), &key("triangles"))?;
+                let mut mesh = Mesh::new(
+                    positions.iter().map(to_float3).collect(),
+                    triangles.clone(),
+                    self.material(name, key("material"))?,
+                );
+                if !normals.is_empty() {
+                    mesh = mesh
+                        .with_normals(normals.iter().map(to_float3).collect())
+                        .ok_or_else(|| invalid(key("normals"), "needs one normal per position"))?;
+                }
+                if !uvs.is_empty() {
+                    mesh = mesh
+                        .with_uvs(uvs.iter().map(|&[u, v]| (u, v)).collect())
+                        .ok_or_else(|| invalid(key("uvs"), "needs one uv per position"))?;
+                }
+                parts.push(Box::new(TriangleMesh::new(mesh)));
+            }
+            ShapeSection::Obj {
+                file,
+                material: name,
+            } => {
+                let model = load_obj(self.dir.join(file)).map_err(|error| SceneErrorKind::Obj {
+                    key: key("file"),
+                    error: Box::new(error),
+                })?;
+                let overridden = name
+                    .as_ref()
+                    .map(|name| self.material(name, key("material")))
+                    .transpose()?;
+                for obj_mesh in model.meshes {
+                    let mut mesh = obj_mesh.mesh;
+                    if let (Some(m), Some(mesh)) = (&overridden, Arc::get_mut(&mut mesh)) {
+                        mesh.material = Arc::clone(m);
+                    }
+                    let id = self.material_ids.len();
+                    self.material_ids
+                        .entry(material_key(&mesh.material))
+                        .or_insert(id);
+                    parts.push(Box::new(TriangleMesh::new(mesh)));
+                }
+            }
+            ShapeSection::Sdf {
+                field,
+                bounds,
+                step_scale,
+                max_steps,
+                material: name,
+            } => {
+                let field = build_field(field, &key("field"))?;
+                if bounds.is_none() && field.bounding_box().is_none() {
+                    return Err(invalid(key("bounds"), "needed for an unbounded field"));
+                }
+                if !(*step_scale > 0.0 && *step_scale <= 1.0) {
+                    return Err(invalid(key("step_scale"), "must be in (0, 1]"));
+                }
+                let mut sdf = Sdf::new(field, self.material(name, key("material"))?)
+                    .with_step_scale(*step_scale)
+                    .with_max_steps(*max_steps);
+                if let Some([min, max]) = bounds {
+                    sdf = sdf.with_bounds(Aabb::from_points([to_float3(min), to_float3(max)]));
+                }
+                parts.push(Box::new(sdf));
+            }
+            ShapeSection::Volume {
+                shape,
+                inside,
+                outside,
+            } => {
+                let medium = |name: &Option<String>, field: &str| {
+                    name.as_ref()
+                        .map(|name| find_medium(&self.media, name, key(field)))
+                        .transpose()
+                };
+                let interface =
+                    MediumInterface::new(medium(inside, "inside")?, medium(outside, "outside")?);
+                let child_key = |field: &str| key(&format!("shape.{}", field));
+                for part in self.build(shape, &child_key)? {
+                    parts.push(Box::new(Volume::new(part, interface.clone())));
+                }
+            }
+            ShapeSection::Csg { operation, shapes } => {
+                if shapes.len() < 2 {
+                    return Err(invalid(key("shapes"), "needs at least two shapes"));
+                }
+                let operation = match operation {
+                    Operation::Union => CsgOperation::Union,
+                    Operation::Intersection => CsgOperation::Intersection,
+                    Operation::Difference => CsgOperation::Difference,
+                };
+                let mut solids = Vec::new();
+                for (i, section) in shapes.iter().enumerate() {
+                    let child_key = |field: &str| key(&format!("shapes[{}].{}", i, field));
+                    let mut parts = self.build(section, &child_key)?;
+                    solids.push(if parts.len() == 1 {
+                        parts.remove(0)
+                    } else {
+                        Box::new(Bvh::new(parts))
+                    });
+                }
+                let mut solids = solids.into_iter();
+                let first = solids.next().unwrap();
+                parts.push(solids.fold(first, |left, right| {
+                    Box::new(Csg::new(operation, left, right))
+                }));
+            }
+            ShapeSection::Instance {
+                prototype,
+                translate,
+                rotate,
+                scale,
+                material: name,
+            } => {
+                let shape = self.prototypes.get(prototype.as_str()).ok_or_else(|| {
+                    invalid(
+                        key("prototype"),
+                        format!("unknown prototype `{}`", prototype),
+                    )
+                })?;
+                let scale = match scale {
+                    ScaleValue::Uniform(s) => Float3::full(*s),
+                    ScaleValue::Axes(s) => to_float3(s),
+                };
+                let transform =
+                    Transform::from_trs(to_float3(translate), to_rotation(rotate), scale)
+                        .ok_or_else(|| invalid(key("scale"), "must not be zero"))?;
+                let mut instance = Instance::new(Arc::clone(shape), transform);
+                if let Some(name) = name {
+                    instance = instance.with_material(self.material(name, key("material"))?);
+                }
+                parts.push(Box::new(instance));
+            }
+        }
+        Ok(parts)
+    }
+}
+
+fn find_medium(
+    media: &HashMap<&str, Arc<dyn Medium>>,
+    name: &str,
+    key: String,
+) -> Result<Arc<dyn Medium>, SceneErrorKind> {
+    media
+        .get(name)
+        .cloned()
+        .ok_or_else(|| invalid(key, format!("unknown medium `{}`", name)))
+}
+
+fn build_medium(
+    section: &MediumSection,
+    key: &str,
+    dir: &Path,
+) -> Result<Arc<dyn Medium>, SceneErrorKind> {
+    let field = |name: &str| format!("{}.{}", key, name);
+    let check_g = |g: f64| {
+        if g.abs() < 1.0 {
+            Ok(())
+        } else {
+            Err(invalid(field("g"), "must be between -1 and 1"))
+        }
+    };
+    Ok(match section {
+        MediumSection::Homogeneous {
+            absorption,
+            scattering,
+            g,
+        } => {
+            let sigma_a = to_color(absorption, &field("absorption"))?;
+            let sigma_s = to_color(scattering, &field("scattering"))?;
+            if sigma_a.iter().chain(sigma_s.iter()).any(|&c| c < 0.0) {
+                return Err(invalid(key, "coefficients must not be negative"));
+            }
+            check_g(*g)?;
+            Arc::new(HomogeneousMedium::new(sigma_a, sigma_s, *g))
+        }
+        MediumSection::Grid {
+            file,
+            noise,
+            resolution,
+            min,
+            max,
+            extinction,
+            albedo,
+            g,
+        } => {
+            if resolution.contains(&0) {
+                return Err(invalid(field("resolution"), "must be positive"));
+            }
+            if (0..3).any(|i| min[i] >= max[i]) {
+                return Err(invalid(
+                    field("max"),
+                    "must be greater than `min` on every axis",
+                ));
+            }
+            if *extinction < 0.0 {
+                return Err(invalid(field("extinction"), "must not be negative"));
+            }
+            check_g(*g)?;
+            let grid =
+                match (file, noise) {
+                    (Some(file), None) => DensityGrid::load_raw(dir.join(file), *resolution)
+                        .map_err(|error| SceneErrorKind::Voxels {
+                            key: field("file"),
+                            error,
+                        })?,
+                    (None, Some(noise)) => {
+                        DensityGrid::noise(*resolution, noise.scale, noise.octaves, noise.seed)
+                    }
+                    _ => return Err(invalid(key, "needs either `file` or `noise`")),
+                };
+            Arc::new(GridMedium::new(
+                grid,
+                Aabb::new(to_float3(min), to_float3(max)),
+                *extinction,
+                to_color(albedo, &field("albedo"))?,
+                *g,
+            ))
+        }
+    })
+}
+
+fn build_field(
+    section: &FieldSection,
+    key: &str,
+) -> Result<Box<dyn DistanceField>, SceneErrorKind> {
+    let field = |name: &str| format!("{}.{}", key, name);
+    Ok(match section {
+        FieldSection::Sphere { center, radius } => {
+            check_positive(*radius, &field("radius"))?;
+            Box::new(SphereSdf::new(to_float3(center), *radius))
+        }
+        FieldSection::Box {
+            center,
+            size,
+            rounding,
+        } => {
+            if size.iter().any(|&s| s <= 0.0) {
+                return Err(invalid(field("size"), "must be positive"));
+            }
+            Box::new(BoxSdf::new(to_float3(center), to_float3(size)).with_rounding(*rounding))
+        }
+        FieldSection::Torus {
+            center,
+            major_radius,
+            minor_radius,
+        } => {
+            check_positive(*major_radius, &field("major_radius"))?;
+            check_positive(*minor_radius, &field("minor_radius"))?;
+            Box::new(TorusSdf::new(
+                to_float3(center),
+                *major_radius,
+                *minor_radius,
+            ))
+        }
+        FieldSection::Capsule { a, b, radius } => {
+            check_positive(*radius, &field("radius"))?;
+            if a == b {
+                return Err(invalid(field("b"), "must differ from `a`"));
+            }
+            Box::new(CapsuleSdf::new(to_float3(a), to_float3(b), *radius))
+        }
+        FieldSection::Cylinder {
+            center,
+            radius,
+            height,
+        } => {
+            check_positive(*radius, &field("radius"))?;
+            check_positive(*height, &field("height"))?;
+            Box::new(CylinderSdf::new(to_float3(center), *radius, *height))
+        }
+        FieldSection::SmoothUnion { fields, smoothness } => {
+            if fields.is_empty() {
+                return Err(invalid(field("fields"), "must not be empty"));
+            }
+            let fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, section)| build_field(section, &field(&format!("fields[{}]", i))))
+                .collect::<Result<_, _>>()?;
+            Box::new(SmoothUnion::new(fields, *smoothness))
+        }
+        FieldSection::Twist { field: inner, rate } => Box::new(Twist::new(
+            build_field(inner, &field("field"))?,
+            rate.to_radians(),
+        )),
+        FieldSection::Repeat {
+            field: inner,
+            period,
+        } => {
+            if period.iter().any(|&p| p < 0.0) {
+                return Err(invalid(field("period"), "must not be negative"));
+            }
+            Box::new(Repeat::new(
+                build_field(inner, &field("field"))?,
+                to_float3(period),
+            ))
+        }
+        FieldSection::Mandelbulb {
+            center,
+            scale,
+            power,
+            iterations,
+        } => {
+            check_positive(*scale, &field("scale"))?;
+            if *power < 2.0 {
+                return Err(invalid(field("power"), "must be at least 2"));
+            }
+            Box::new(
+                Mandelbulb::new(to_float3(center), *scale)
+                    .with_power(*power)
+                    .with_iterations(*iterations),
+            )
+        }
+    })
+}
+
+/// Identifies a material by the address it is shared at
+fn material_key(material: &Arc<dyn Material>) -> usize {
+    Arc::as_ptr(material) as *const () as usize
+}
+
+fn invalid(key: impl Into<String>, message: impl Into<String>) -> SceneErrorKind {
+    SceneErrorKind::Invalid {
+        key: key.into(),
+        message: message.into(),
+    }
+}
+
+fn check_triangles(
+    triangles: &[[usize; 3]],
+    vertex_count: usize,
+    key: &str,
+) -> Result<(), SceneErrorKind> {
+    match triangles.iter().flatten().find(|&&i| i >= vertex_count) {
+        Some(index) => Err(invalid(key, format!("vertex index {} out of range", index))),
+        None => Ok(()),
+    }
+}
+
+fn check_positive(value: f64, key: &str) -> Result<(), SceneErrorKind> {
+    if value > 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(key, "must be positive"))
+    }
+}
+
+/// Unit vector, an error if it has no length
+fn to_direction(v: &[f64; 3], key: &str) -> Result<Float3, SceneErrorKind> {
+    let v = to_float3(v);
+    if v.near_zero() {
+        return Err(invalid(key, "must not be zero"));
+    }
+    Ok(v.normalize())
+}
+
+/// Rotation from angles in degrees around the x, y and z axes, applied in that order
+fn to_rotation(degrees: &[f64; 3]) -> Quaternion {
+    let [rx, ry, rz] = degrees.map(f64::to_radians);
+    Quaternion::from_rot_z(rz) * Quaternion::from_rot_y(ry) * Quaternion::from_rot_x(rx)
+}
+
+fn to_float3(v: &[f64; 3]) -> Float3 {
+    Float3::new(v[0], v[1], v[2])
+}
+
+fn to_color(value: &ColorValue, key: &str) -> Result<Color, SceneErrorKind> {
+    match value {
+        ColorValue::Rgb(rgb) => Ok(to_float3(rgb)),
+        ColorValue::Gray(gray) => Ok(Color::full(*gray)),
+        ColorValue::Hex(hex) => {
+            let digits = hex.strip_prefix('#').unwrap_or(hex).as_bytes();
+            match <&[u8; 6]>::try_from(digits) {
+                Ok(digits) if digits.iter().all(u8::is_ascii_hexdigit) => {
+                    // 16進表記は sRGB とみなして線形に戻す
+                    Ok(Color::from_hex(digits).degamma(2.2))
+                }
+                _ => Err(invalid(key, format!("invalid hex color `{}`", hex))),
+            }
+        }
+        ColorValue::Texture { .. } => Err(invalid(key, "textures are not allowed here")),
+    }
+}
+
+/// Like `to_color`, but also accepts references to textures
+fn to_texture(
+    value: &ColorValue,
+    textures: &HashMap<&str, Arc<dyn Texture>>,
+    key: &str,
+) -> Result<Arc<dyn Texture>, SceneErrorKind> {
+    match value {
+        ColorValue::Texture { texture } => textures
+            .get(texture.as_str())
+            .cloned()
+            .ok_or_else(|| invalid(key, format!("unknown texture `{}`", texture))),
+        _ => Ok(Arc::new(SolidColor::new(to_color(value, key)?))),
+    }
+}
+
+fn build_texture(
+    section: &TextureSection,
+    key: &str,
+    dir: &Path,
+) -> Result<Arc<dyn Texture>, SceneErrorKind> {
+    let field = |name: &str| format!("{}.{}", key, name);
+    let positive = |value: f64, name: &str| {
+        if value > 0.0 {
+            Ok(value)
+        } else {
+            Err(invalid(field(name), "must be positive"))
+        }
+    };
+    let colors = |colors: &Option<[ColorValue; 2]>| {
+        colors
+            .as_ref()
+            .map(|[a, b]| {
+                Ok::<_, SceneErrorKind>((
+                    to_color(a, &field("colors"))?,
+                    to_color(b, &field("colors"))?,
+                ))
+            })
+            .transpose()
+    };
+    Ok(match section {
+        TextureSection::Image {
+            file,
+            gamma,
+            wrap,
+            filter,
+            scale: [su, sv],
+        } => {
+            let gamma = positive(*gamma, "gamma")?;
+            let image = ImageTexture::load(dir.join(file), gamma).map_err(|error| {
+                SceneErrorKind::Image {
+                    key: field("file"),
+                    error: Box::new(error),
+                }
+            })?;
+            let wrap = match wrap {
+                Wrap::Repeat => WrapMode::Repeat,
+                Wrap::Mirror => WrapMode::Mirror,
+                Wrap::Clamp => WrapMode::Clamp,
+            };
+            let filter = match filter {
+                ImageFilter::Nearest => Filter::Nearest,
+                ImageFilter::Bilinear => Filter::Bilinear,
+            };
+            Arc::new(
+                image
+                    .with_wrap(wrap)
+                    .with_filter(filter)
+                    .with_scale(*su, *sv),
+            )
+        }
+        TextureSection::Checker {
+            even,
+            odd,
+            scale,
+            space,
+        } => {
+            let space = match space {
+                Space::World => TextureSpace::World,
+                Space::Uv => TextureSpace::Uv,
+            };
+            Arc::new(
+                CheckerTexture::new(
+                    to_color(even, &field("even"))?,
+                    to_color(odd, &field("odd"))?,
+                    positive(*scale, "scale")?,
+                )
+                .with_space(space),
+            )
+        }
+        TextureSection::Noise {
+            scale,
+            octaves,
+            colors: pair,
+            seed,
+        } => {
+            let mut texture = NoiseTexture::new(positive(*scale, "scale")?)
+                .with_octaves(*octaves)
+                .with_seed(*seed);
+            if let Some((low, high)) = colors(pair)? {
+                texture = texture.with_colors(low, high);
+            }
+            Arc::new(texture)
+        }
+        TextureSection::Worley {
+            scale,
+            feature,
+            colors: pair,
+            seed,
+        } => {
+            let feature = match feature {
+                Feature::F1 => WorleyFeature::F1,
+                Feature::Edge => WorleyFeature::Edge,
+            };
+            let mut texture = WorleyTexture::new(positive(*scale, "scale")?)
+                .with_feature(feature)
+                .with_seed(*seed);
+            if let Some((low, high)) = colors(pair)? {
+                texture = texture.with_colors(low, high);
+            }
+            Arc::new(texture)
+        }
+        TextureSection::Marble {
+            scale,
+            turbulence,
+            octaves,
+            colors: pair,
+            seed,
+        } => {
+            let mut texture = MarbleTexture::new(positive(*scale, "scale")?)
+                .with_octaves(*octaves)
+                .with_seed(*seed);
+            if let Some(turbulence) = turbulence {
+                texture = texture.with_turbulence(*turbulence);
+            }
+            if let Some((vein, base)) = colors(pair)? {
+                texture = texture.with_colors(vein, base);
+            }
+            Arc::new(texture)
+        }
+        TextureSection::Wood {
+            scale,
+            turbulence,
+            colors: pair,
+            seed,
+        } => {
+            let mut texture = WoodTexture::new(positive(*scale, "scale")?).with_seed(*seed);
+            if let Some(turbulence) = turbulence {
+                texture = texture.with_turbulence(*turbulence);
+            }
+            if let Some((ring, base)) = colors(pair)? {
+                texture = texture.with_colors(ring, base);
+            }
+            Arc::new(texture)
+        }
+    })
+}
+
+fn build_material(
+    section: &MaterialSection,
+    key: &str,
+    textures: &HashMap<&str, Arc<dyn Texture>>,
+) -> Result<Arc<dyn Material>, SceneErrorKind> {
+    let field = |name: &str| format!("{}.{}", key, name);
+    Ok(match section {
+        MaterialSection::Lambertian { albedo } => Arc::new(Lambertian::from_texture(to_texture(
+            albedo,
+            textures,
+            &field("albedo"),
+        )?)),
+        MaterialSection::Metal { albedo, fuzz } => Arc::new(Metal::from_texture(
+            to_texture(albedo, textures, &field("albedo"))?,
+            *fuzz,
+        )),
+        MaterialSection::Dielectric { ior } => {
+            if *ior <= 0.0 {
+                return Err(invalid(field("ior"), "must be positive"));
+            }
+            Arc::new(Dielectric::new(*ior))
+        }
+        MaterialSection::DiffuseLight { emit, intensity } => Arc::new(DiffuseLight::new(
+            to_color(emit, &field("emit"))? * *intensity,
+        )),
+        MaterialSection::Principled(section) => Arc::new(build_principled(section, key, textures)?),
+        MaterialSection::Subsurface {
+            albedo,
+            mean_free_path,
+            ior,
+            roughness,
+            g,
+        } => {
+            let mean_free_path = to_color(mean_free_path, &field("mean_free_path"))?;
+            if mean_free_path.iter().any(|&d| d <= 0.0) {
+                return Err(invalid(field("mean_free_path"), "must be positive"));
+            }
+            if *ior <= 0.0 {
+                return Err(invalid(field("ior"), "must be positive"));
+            }
+            if !(0.0..=1.0).contains(roughness) {
+                return Err(invalid(field("roughness"), "must be between 0 and 1"));
+            }
+            if g.abs() >= 1.0 {
+                return Err(invalid(field("g"), "must be between -1 and 1"));
+            }
+            Arc::new(
+                Subsurface::from_texture(
+                    to_texture(albedo, textures, &field("albedo"))?,
+                    mean_free_path,
+                )
+                .with_ior(*ior)
+                .with_roughness(*roughness)
+                .with_anisotropy(*g),
+            )
+        }
+        MaterialSection::Interface => Arc::new(InterfaceMaterial),
+    })
+}
+
+fn build_principled(
+    section: &PrincipledSection,
+    key: &str,
+    textures: &HashMap<&str, Arc<dyn Texture>>,
+) -> Result<Principled, SceneErrorKind> {
+    let field = |name: &str| format!("{}.{}", key, name);
+    let has_conductor = section.conductor.is_some() || section.eta.is_some() || section.k.is_some();
+    let metallic = section
+        .metallic
+        .unwrap_or(if has_conductor { 1.0 } else { 0.0 });
+    let factors = [
+        ("metallic", metallic),
+        ("roughness", section.roughness),
+        ("anisotropic", section.anisotropic),
+        ("specular", section.specular),
+        ("specular_tint", section.specular_tint),
+        ("sheen", section.sheen),
+        ("sheen_tint", section.sheen_tint),
+        ("clearcoat", section.clearcoat),
+        ("clearcoat_roughness", section.clearcoat_roughness),
+    ];
+    if let Some((name, _)) = factors.iter().find(|(_, v)| !(0.0..=1.0).contains(v)) {
+        return Err(invalid(field(name), "must be between 0 and 1"));
+    }
+
+    let conductor = match (&section.conductor, section.eta, section.k) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+            return Err(invalid(
+                field("conductor"),
+                "give either a metal name or `eta` and `k`",
+            ))
+        }
+        (Some(name), None, None) => Some(conductors::by_name(name).ok_or_else(|| {
+            invalid(
+                field("conductor"),
+                format!(
+                    "unknown metal `{}`, expected one of gold, silver, copper, aluminium",
+                    name
+                ),
+            )
+        })?),
+        (None, Some(eta), Some(k)) => Some((to_float3(&eta), to_float3(&k))),
+        (None, Some(_), None) => return Err(invalid(field("k"), "is required with `eta`")),
+        (None, None, Some(_)) => return Err(invalid(field("eta"), "is required with `k`")),
+        (None, None, None) => None,
+    };
+
+    let (base_color, base_color_texture) = match &section.base_color {
+        ColorValue::Texture { .. } => (
+            Color::one(),
+            Some(to_texture(
+                &section.base_color,
+                textures,
+                &field("base_color"),
+            )?),
+        ),
+        color => (to_color(color, &field("base_color"))?, None),
+    };
+    Ok(Principled {
+        base_color,
+        base_color_texture,
+        metallic,
+        roughness: section.roughness,
+        anisotropic: section.anisotropic,
+        anisotropic_rotation: section.anisotropic_rotation,
+        specular: section.specular,
+        specular_tint: section.specular_tint,
+        sheen: section.sheen,
+        sheen_tint: section.sheen_tint,
+        clearcoat: section.clearcoat,
+        clearcoat_roughness: section.clearcoat_roughness,
+        conductor,
+    })
+}
+
+impl World for FileScene {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let mut hit = self.objects.hit(ray, t0, t1)?;
+        hit.material_id = self.material_ids.get(&material_key(&hit.material)).copied();
+        Some(hit)
+    }
+
+    fn environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+
+    fn lights(&self) -> &[Box<dyn Light>] {
+        &self.lights
+    }
+
+    fn medium(&self) -> Option<Arc<dyn Medium>> {
+        self.medium.clone()
+    }
+}
+
+impl Scene for FileScene {
+    fn camera(&self) -> Box<dyn Camera> {
+        let section = &self.camera;
+        let (origin, lookat, up) = (
+            to_float3(&section.origin),
+            to_float3(&section.lookat),
+            to_float3(&section.up),
+        );
+        match section.projection {
+            Projection::Perspective => {}
+            Projection::Orthographic => {
+                return Box::new(OrthographicCamera::from_lookat(
+                    origin,
+                    lookat,
+                    up,
+                    section.view_height,
+                    self.aspect(),
+                ))
+            }
+            Projection::Fisheye => {
+                return Box::new(FisheyeCamera::from_lookat(
+                    origin,
+                    lookat,
+                    up,
+                    section.fov,
+                    section.mapping,
+                    self.aspect(),
+                ));
+            }
+            Projection::Equirectangular => {
+                return Box::new(EquirectangularCamera::from_lookat(origin, lookat, up))
+            }
+        }
+
+        let camera = PerspectiveCamera::from_lookat(origin, lookat, up, section.fov, self.aspect());
+        if section.aperture <= 0.0 {
+            return Box::new(camera);
+        }
+
+        let aperture = match section.blades {
+            Some(blades) => Aperture::Polygon {
+                blades,
+                rotation: section.blade_rotation,
+            },
+            None => Aperture::Circle,
+        };
+        let focus_distance = section
+            .focus_distance
+            .unwrap_or_else(|| (lookat - origin).length());
+        let camera = camera
+            .with_lens(section.aperture, focus_distance)
+            .with_aperture(aperture);
+        if section.autofocus {
+            Box::new(camera.autofocus(self))
+        } else {
+            Box::new(camera)
+        }
+    }
+
+    fn trace(&self, ray: Ray) -> Color {
+        PathTracer::new(self.settings.max_depth).trace(self, ray)
+    }
+
+    fn width(&self) -> u32 {
+        self.settings.width
+    }
+
+    fn height(&self) -> u32 {
+        self.settings.height
+    }
+
+    fn spp(&self) -> u32 {
+        self.settings.spp
+    }
+
+    fn sampler(&self) -> SamplerKind {
+        self.settings.sampler
+    }
+}
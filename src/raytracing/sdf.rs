@@ -0,0 +1,467 @@
+//! Shapes given by signed distance functions, rendered by sphere tracing.
+//!
+//! Fields are built from the primitives here and the combinators that wrap other fields, and
+//! become a `Shape` through `Sdf`.
+
+use std::sync::Arc;
+
+use super::{
+    aabb::Aabb, hit_info::HitInfo, material::Material, ray::Ray, shapes::Shape, Point3, Vec3,
+};
+
+pub trait DistanceField: Sync + Send {
+    /// Signed distance from `p` to the surface, negative inside. A lower bound of the distance
+    /// is enough, it only shortens the steps
+    fn distance(&self, p: Point3) -> f64;
+    /// Returns the bounds of the surface, or None if it is unbounded
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+fn abs(v: Vec3) -> Vec3 {
+    Vec3::from_iter(v.iter().map(|x| x.abs()))
+}
+
+pub struct SphereSdf {
+    center: Point3,
+    radius: f64,
+}
+
+impl SphereSdf {
+    pub const fn new(center: Point3, radius: f64) -> Self {
+        Self { center, radius }
+    }
+}
+
+impl DistanceField for SphereSdf {
+    fn distance(&self, p: Point3) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::full(self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+/// Axis-aligned box, optionally with rounded edges
+pub struct BoxSdf {
+    center: Point3,
+    half_size: Vec3,
+    rounding: f64,
+}
+
+impl BoxSdf {
+    /// Box of the given edge lengths
+    pub fn new(center: Point3, size: Vec3) -> Self {
+        Self {
+            center,
+            half_size: abs(size) * 0.5,
+            rounding: 0.0,
+        }
+    }
+
+    /// Round the edges with `radius`, the box keeps its size
+    pub fn with_rounding(mut self, radius: f64) -> Self {
+        self.rounding = radius.clamp(0.0, self.half_size.iter().copied().fold(f64::MAX, f64::min));
+        self
+    }
+}
+
+impl DistanceField for BoxSdf {
+    fn distance(&self, p: Point3) -> f64 {
+        let q = abs(p - self.center) - self.half_size + Vec3::full(self.rounding);
+        q.max(Vec3::zero()).length() + q.max_element().min(0.0) - self.rounding
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            self.center - self.half_size,
+            self.center + self.half_size,
+        ))
+    }
+}
+
+/// Torus around the y axis
+pub struct TorusSdf {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl TorusSdf {
+    pub const fn new(center: Point3, major_radius: f64, minor_radius: f64) -> Self {
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl DistanceField for TorusSdf {
+    fn distance(&self, p: Point3) -> f64 {
+        let p = p - self.center;
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.major_radius;
+        (ring * ring + p.y() * p.y()).sqrt() - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.major_radius + self.minor_radius;
+        let h = Vec3::new(r, self.minor_radius, r);
+        Some(Aabb::new(self.center - h, self.center + h))
+    }
+}
+
+/// Segment from `a` to `b` thickened by `radius`
+pub struct CapsuleSdf {
+    a: Point3,
+    b: Point3,
+    radius: f64,
+}
+
+impl CapsuleSdf {
+    pub const fn new(a: Point3, b: Point3, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl DistanceField for CapsuleSdf {
+    fn distance(&self, p: Point3) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(ba) / ba.length_squared()).clamp(0.0, 1.0);
+        (pa - ba * h).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::full(self.radius);
+        Some(Aabb::from_points([
+            self.a - r,
+            self.a + r,
+            self.b - r,
+            self.b + r,
+        ]))
+    }
+}
+
+/// Capped cylinder along the y axis, centered on `center`
+pub struct CylinderSdf {
+    center: Point3,
+    radius: f64,
+    height: f64,
+}
+
+impl CylinderSdf {
+    pub const fn new(center: Point3, radius: f64, height: f64) -> Self {
+        Self {
+            center,
+            radius,
+            height,
+        }
+    }
+}
+
+impl DistanceField for CylinderSdf {
+    fn distance(&self, p: Point3) -> f64 {
+        let p = p - self.center;
+        let dx = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.radius;
+        let dy = p.y().abs() - 0.5 * self.height;
+        dx.max(dy).min(0.0) + dx.max(0.0).hypot(dy.max(0.0))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let h = Vec3::new(self.radius, 0.5 * self.height, self.radius);
+        Some(Aabb::new(self.center - h, self.center + h))
+    }
+}
+
+/// Union that blends the fields into each other over about `smoothness`
+pub struct SmoothUnion {
+    fields: Vec<Box<dyn DistanceField>>,
+    smoothness: f64,
+}
+
+impl SmoothUnion {
+    pub fn new(fields: Vec<Box<dyn DistanceField>>, smoothness: f64) -> Self {
+        Self {
+            fields,
+            smoothness: smoothness.max(0.0),
+        }
+    }
+}
+
+impl DistanceField for SmoothUnion {
+    fn distance(&self, p: Point3) -> f64 {
+        let k = self.smoothness;
+        self.fields
+            .iter()
+            .map(|field| field.distance(p))
+            .reduce(|a, b| {
+                if k == 0.0 {
+                    return a.min(b);
+                }
+                // 多項式による滑らかな min
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+                b + (a - b) * h - k * h * (1.0 - h)
+            })
+            .unwrap_or(f64::MAX)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // 滑らかな min は min より最大 k/4 小さいので、その分だけ広げる
+        let pad = Vec3::full(0.25 * self.smoothness);
+        self.fields
+            .iter()
+            .try_fold(Aabb::empty(), |aabb, field| {
+                field.bounding_box().map(|b| aabb.union(b))
+            })
+            .map(|b| Aabb::new(b.min - pad, b.max + pad))
+    }
+}
+
+/// Twists a field around the y axis by `rate` radians per unit of height.
+///
+/// Twisting stretches distances, so shapes twisted strongly need `Sdf::with_step_scale`.
+pub struct Twist {
+    field: Box<dyn DistanceField>,
+    rate: f64,
+}
+
+impl Twist {
+    pub fn new(field: Box<dyn DistanceField>, rate: f64) -> Self {
+        Self { field, rate }
+    }
+}
+
+impl DistanceField for Twist {
+    fn distance(&self, p: Point3) -> f64 {
+        let (s, c) = (-self.rate * p.y()).sin_cos();
+        self.field.distance(Point3::new(
+            c * p.x() - s * p.z(),
+            p.y(),
+            s * p.x() + c * p.z(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // y 軸まわりに回しても収まるように、水平方向は外接円で囲む
+        let b = self.field.bounding_box()?;
+        let r = [b.min.x(), b.max.x()]
+            .into_iter()
+            .flat_map(|x| [b.min.z(), b.max.z()].map(|z| x.hypot(z)))
+            .fold(0.0, f64::max);
+        Some(Aabb::new(
+            Point3::new(-r, b.min.y(), -r),
+            Point3::new(r, b.max.y(), r),
+        ))
+    }
+}
+
+/// Repeats a field forever with the given period along each axis, 0 leaves an axis alone.
+///
+/// The field should fit in one cell around the origin.
+pub struct Repeat {
+    field: Box<dyn DistanceField>,
+    period: Vec3,
+}
+
+impl Repeat {
+    pub fn new(field: Box<dyn DistanceField>, period: Vec3) -> Self {
+        Self {
+            field,
+            period: abs(period),
+        }
+    }
+}
+
+impl DistanceField for Repeat {
+    fn distance(&self, p: Point3) -> f64 {
+        let q = Point3::from_iter((0..3).map(|i| {
+            let period = self.period[i];
+            if period > 0.0 {
+                p[i] - period * (p[i] / period).round()
+            } else {
+                p[i]
+            }
+        }));
+        self.field.distance(q)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.period.iter().any(|&period| period > 0.0) {
+            return None;
+        }
+        self.field.bounding_box()
+    }
+}
+
+/// Mandelbulb fractal, using the distance estimate from the derivative of the iteration
+pub struct Mandelbulb {
+    center: Point3,
+    scale: f64,
+    power: f64,
+    iterations: u32,
+}
+
+impl Mandelbulb {
+    /// The fractal fits in a sphere of about `scale` * 1.2 around `center`
+    pub const fn new(center: Point3, scale: f64) -> Self {
+        Self {
+            center,
+            scale,
+            power: 8.0,
+            iterations: 12,
+        }
+    }
+
+    pub const fn with_power(mut self, power: f64) -> Self {
+        self.power = power;
+        self
+    }
+
+    pub const fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+}
+
+impl DistanceField for Mandelbulb {
+    fn distance(&self, p: Point3) -> f64 {
+        let c = (p - self.center) / self.scale;
+        let n = self.power;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            if r > 2.0 {
+                break;
+            }
+            // 極座標で z^n + c を計算する。極は y 軸
+            let theta = (z.y() / r).clamp(-1.0, 1.0).acos() * n;
+            let phi = z.z().atan2(z.x()) * n;
+            let rn1 = r.powf(n - 1.0);
+            dr = rn1 * n * dr + 1.0;
+            let zr = rn1 * r;
+            z = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            ) * zr
+                + c;
+            r = z.length();
+            if r == 0.0 {
+                return 0.0;
+            }
+        }
+        0.5 * r.ln() * r / dr * self.scale
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::full(1.2 * self.scale);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+/// Surface of a distance field, found by sphere tracing.
+///
+/// Normals come from finite differences of the field. The hit is reported just before the
+/// surface, on the side the ray came from.
+pub struct Sdf {
+    field: Box<dyn DistanceField>,
+    material: Arc<dyn Material>,
+    bounds: Option<Aabb>,
+    epsilon: f64,
+    max_steps: u32,
+    step_scale: f64,
+}
+
+impl Sdf {
+    pub fn new(field: Box<dyn DistanceField>, material: Arc<dyn Material>) -> Self {
+        let bounds = field.bounding_box();
+        Self {
+            field,
+            material,
+            bounds,
+            epsilon: 1e-4,
+            max_steps: 256,
+            step_scale: 1.0,
+        }
+    }
+
+    /// Limit the search to a box, needed for fields that repeat forever
+    pub fn with_bounds(mut self, bounds: Aabb) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// Distance to the surface that counts as a hit
+    pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Shorten every step by this factor, for fields that overestimate the distance
+    pub fn with_step_scale(mut self, step_scale: f64) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    /// Gradient of the field by central differences on a tetrahedron
+    fn normal(&self, p: Point3) -> Vec3 {
+        let h = self.epsilon;
+        [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ]
+        .into_iter()
+        .fold(Vec3::zero(), |n, k| n + k * self.field.distance(p + k * h))
+        .normalize()
+    }
+}
+
+impl Shape for Sdf {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let (start, end) = match &self.bounds {
+            Some(bounds) => bounds.clip(ray, t0, t1)?,
+            None => (t0, t1),
+        };
+        let length = ray.direction.length();
+
+        let mut t = start;
+        let distance = self.field.distance(ray.at(t));
+        // 始点の側を正とした距離で進む
+        let mut side = if distance < 0.0 { -1.0 } else { 1.0 };
+        // 直前の交差点から出たレイは、表面の近くを抜けるまで当たりとみなさない
+        let mut escaped = distance.abs() >= self.epsilon;
+        for _ in 0..self.max_steps {
+            let mut d = side * self.field.distance(ray.at(t));
+            if !escaped {
+                if d < 0.0 {
+                    // 出発した表面を通り抜けたので反対側から測り直す
+                    side = -side;
+                    d = -d;
+                }
+                escaped = d >= self.epsilon;
+            } else if d < self.epsilon {
+                let normal = self.normal(ray.at(t));
+                return Some(HitInfo::new(ray, t, normal, Arc::clone(&self.material)));
+            }
+            t += (d * self.step_scale).max(self.epsilon) / length;
+            if t >= end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
@@ -1,21 +1,82 @@
+use std::sync::Arc;
+
 use super::{
-    hit_info::{self, HitInfo},
+    aabb::Aabb,
+    csg::{spans_from_hits, Span},
+    hit_info::HitInfo,
+    material::Material,
     ray::Ray,
-    Point3,
+    Point3, Vec3, PI, PI2,
 };
 
-pub trait Shape: Sync {
+pub trait Shape: Sync + Send {
     fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo>;
+    /// Returns the bounds of the shape, or None if it is unbounded
+    fn bounding_box(&self) -> Option<Aabb>;
+    /// Returns the parts of the ray within (t0, t1) inside the shape, in order, for CSG.
+    ///
+    /// The default follows the hits along the ray, which is right for closed shapes.
+    fn intervals(&self, ray: &Ray, t0: f64, t1: f64) -> Vec<Span> {
+        spans_from_hits(self, ray, t0, t1)
+    }
+}
+
+/// Shared shapes, so geometry can be referenced from several places
+impl<S: Shape + ?Sized> Shape for Arc<S> {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        self.as_ref().hit(ray, t0, t1)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.as_ref().bounding_box()
+    }
+
+    fn intervals(&self, ray: &Ray, t0: f64, t1: f64) -> Vec<Span> {
+        self.as_ref().intervals(ray, t0, t1)
+    }
 }
 
 pub struct Sphere {
     center: Point3,
     radius: f64,
+    material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub const fn new(center: Point3, radius: f64) -> Self {
-        Self { center, radius }
+    pub const fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+        }
+    }
+
+    /// Texture coordinates of a point on the unit sphere, u goes around the y axis
+    /// starting at -x and v from the bottom to the top
+    pub fn uv(p: Vec3) -> (f64, f64) {
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / PI2, theta / PI)
+    }
+
+    fn hit_info(&self, ray: &Ray, t: f64) -> HitInfo {
+        let offset = ray.at(t) - self.center;
+        let hit = HitInfo::new(ray, t, offset / self.radius, Arc::clone(&self.material));
+        let r = self.radius.abs();
+        let p = offset / r;
+        let (u, v) = Self::uv(p);
+        let sin_theta = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        if sin_theta < 1e-9 {
+            // 極では u 方向が定まらないので既定の接線のままにする
+            return hit.with_uv(u, v);
+        }
+        let dpdu = Vec3::new(p.z(), 0.0, -p.x()) * (PI2 * r);
+        let dpdv = Vec3::new(
+            -p.x() * p.y() / sin_theta,
+            sin_theta,
+            -p.z() * p.y() / sin_theta,
+        ) * (PI * r);
+        hit.with_uv(u, v).with_tangents(dpdu, dpdv)
     }
 }
 
@@ -32,24 +93,121 @@ impl Shape for Sphere {
             let root = d.sqrt();
             let temp = (-b - root) / (2.0 * a);
             if t0 < temp && temp < t1 {
-                let p = ray.at(temp);
-                return Some(HitInfo::new(temp, p, (p - self.center) / self.radius));
+                return Some(self.hit_info(ray, temp));
             }
             let temp = (-b + root) / (2.0 * a);
             if t0 < temp && temp < t1 {
-                let p = ray.at(temp);
-                return Some(HitInfo::new(temp, p, (p - self.center) / self.radius));
+                return Some(self.hit_info(ray, temp));
             }
         }
 
         None
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::full(self.radius.abs());
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub const fn new(v0: Point3, v1: Point3, v2: Point3, material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            material,
+        }
+    }
+}
+
+/// Watertight ray-triangle intersection (Woop, Benthin and Wald 2013).
+///
+/// Returns the distance and the barycentric weights of `p0`, `p1` and `p2`.
+pub fn intersect_triangle(
+    ray: &Ray,
+    [p0, p1, p2]: [Point3; 3],
+    t0: f64,
+    t1: f64,
+) -> Option<(f64, [f64; 3])> {
+    let d = ray.direction;
+
+    // レイの向きの成分が最大の軸を z 軸とする座標系に変換する
+    let kz = if d.x().abs() > d.y().abs() && d.x().abs() > d.z().abs() {
+        0
+    } else if d.y().abs() > d.z().abs() {
+        1
+    } else {
+        2
+    };
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if d[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+    let sx = d[kx] / d[kz];
+    let sy = d[ky] / d[kz];
+    let sz = d[kz].recip();
+
+    let a = p0 - ray.origin;
+    let b = p1 - ray.origin;
+    let c = p2 - ray.origin;
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
+
+    // 辺関数の符号がそろっていれば三角形の内側
+    let e0 = cx * by - cy * bx;
+    let e1 = ax * cy - ay * cx;
+    let e2 = bx * ay - by * ax;
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t_scaled = (e0 * a[kz] + e1 * b[kz] + e2 * c[kz]) * sz;
+    let t = t_scaled / det;
+    if t <= t0 || t >= t1 {
+        return None;
+    }
+
+    Some((t, [e0 / det, e1 / det, e2 / det]))
+}
+
+impl Shape for Triangle {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        let (t, barycentric) = intersect_triangle(ray, self.vertices, t0, t1)?;
+        let [p0, p1, p2] = self.vertices;
+        let normal = (p1 - p0).cross(p2 - p0).normalize();
+        Some(
+            HitInfo::new(ray, t, normal, Arc::clone(&self.material))
+                .with_uv(barycentric[1], barycentric[2])
+                .with_tangents(p1 - p0, p2 - p0)
+                .with_barycentric(barycentric),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.vertices))
+    }
 }
 
+/// Shapes tested one after another, hits report the index of the shape as their `shape_id`
 pub struct ShapeList {
     pub objects: Vec<Box<dyn Shape>>,
 }
 
+impl Default for ShapeList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ShapeList {
     pub fn new() -> Self {
         Self {
@@ -66,15 +224,22 @@ impl Shape for ShapeList {
     fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
         let mut hit_info: Option<HitInfo> = None;
         let mut closet_so_far = t1;
-        for objects in &self.objects {
-            if let Some(info) = objects.hit(ray, t0, closet_so_far) {
+        for (id, objects) in self.objects.iter().enumerate() {
+            if let Some(mut info) = objects.hit(ray, t0, closet_so_far) {
                 closet_so_far = info.length;
+                info.shape_id = Some(id);
                 hit_info = Some(info);
             }
         }
 
         hit_info
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.objects.iter().try_fold(Aabb::empty(), |aabb, object| {
+            object.bounding_box().map(|b| aabb.union(b))
+        })
+    }
 }
 
 impl std::ops::Index<usize> for ShapeList {
@@ -1,60 +1,98 @@
+use std::sync::Arc;
+
 use super::{
-    camera::Camera,
+    bvh::Bvh,
+    camera::{Camera, PerspectiveCamera},
+    environment::{Environment, GradientEnvironment},
+    hit_info::HitInfo,
+    material::{Dielectric, Lambertian, Metal},
+    primitives::Plane,
     ray::Ray,
-    render::Scene,
+    render::{PathTracer, RenderSettings, Scene, World},
+    sampler::SamplerKind,
     shapes::{Shape, ShapeList, Sphere},
-    Color, Float3, Point3, Vec3,
+    Color, Point3, Vec3,
 };
 
 pub struct SimpleScene {
-    objects: ShapeList,
+    pub settings: RenderSettings,
+    objects: Bvh,
+    environment: GradientEnvironment,
+}
+
+impl Default for SimpleScene {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SimpleScene {
     pub fn new() -> Self {
         let mut objects = ShapeList::new();
-        objects.push(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5)));
-        objects.push(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0)));
-        Self { objects }
-    }
-
-    fn hit_sphere(&self, center: Point3, radius: f64, ray: &Ray) -> f64 {
-        let oc = ray.origin - center;
-        let a = ray.direction.dot(ray.direction);
-        let b = 2.0 * ray.direction.dot(oc);
-        let c = oc.dot(oc) - radius.powi(2);
-        let d = b * b - 4.0 * a * c;
-
-        // 判別式
-        if d < 0.0 {
-            -1.0
-        } else {
-            (-b - -d.sqrt()) / (2.0 * a)
+        objects.push(Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5))),
+        )));
+        objects.push(Box::new(Plane::new(
+            Point3::new(0.0, -0.5, 0.0),
+            Vec3::yaxis(),
+            Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))),
+        )));
+        objects.push(Box::new(Sphere::new(
+            Point3::new(1.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.2)),
+        )));
+        objects.push(Box::new(Sphere::new(
+            Point3::new(-1.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Dielectric::new(1.5)),
+        )));
+        Self {
+            settings: RenderSettings::default(),
+            objects: Bvh::from(objects),
+            environment: GradientEnvironment::new(Color::one(), Color::new(0.5, 0.7, 1.0)),
         }
     }
+}
+
+impl World for SimpleScene {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        self.objects.hit(ray, t0, t1)
+    }
 
-    fn background(&self, d: Vec3) -> Color {
-        let t = 0.5 * (d.normalize().y() + 1.0);
-        Color::one().lerp(Color::new(0.5, 0.7, 1.0), t)
+    fn environment(&self) -> &dyn Environment {
+        &self.environment
     }
 }
 
 impl Scene for SimpleScene {
-    fn camera(&self) -> Camera {
-        Camera::new(
+    fn camera(&self) -> Box<dyn Camera> {
+        Box::new(PerspectiveCamera::new(
             Vec3::new(4.0, 0.0, 0.0),
             Vec3::new(0.0, 2.0, 0.0),
             Vec3::new(-2.0, -1.0, -1.0),
-        )
+        ))
     }
 
     fn trace(&self, ray: Ray) -> Color {
-        let hit_info = self.objects.hit(&ray, 0.0, f64::MAX);
-        if let Some(hit) = hit_info {
-            0.8 * (hit.length * Vec3::one())
-            // Color::new(1.0, 0.0, 0.0)
-        } else {
-            self.background(ray.direction)
-        }
+        PathTracer::new(self.settings.max_depth).trace(self, ray)
+    }
+
+    fn width(&self) -> u32 {
+        self.settings.width
+    }
+
+    fn height(&self) -> u32 {
+        self.settings.height
+    }
+
+    fn spp(&self) -> u32 {
+        self.settings.spp
+    }
+
+    fn sampler(&self) -> SamplerKind {
+        self.settings.sampler
     }
 }
@@ -0,0 +1,177 @@
+//! Random-walk subsurface scattering for translucent materials such as skin, wax, marble
+//! and milk.
+//!
+//! Light refracted into a closed shape of a `Subsurface` material scatters through the inside
+//! as in a homogeneous medium until it leaves the shape, usually some way from where it
+//! entered. The path tracer follows the walk, see `Material::subsurface`.
+
+use std::sync::Arc;
+
+use super::{
+    hit_info::HitInfo,
+    material::{texture_value, Material, ScatterInfo},
+    medium::HomogeneousMedium,
+    microfacet::{cosine_hemisphere, Ggx, Onb},
+    ray::Ray,
+    rng::random,
+    texture::{SolidColor, Texture},
+    Color, Vec3, FRAC_1_PI,
+};
+
+/// Translucent material with a glossy dielectric surface.
+///
+/// Paths entering it leave diffusely at the point where their walk through the inside ends.
+/// The shapes must be closed and have outward normals.
+pub struct Subsurface {
+    albedo: Arc<dyn Texture>,
+    mean_free_path: Color,
+    ior: f64,
+    roughness: f64,
+    g: f64,
+}
+
+impl Subsurface {
+    /// `albedo` is the color of the material once light has scattered many times inside it
+    /// and `mean_free_path` how far light travels between scattering events in each channel
+    pub fn new(albedo: Color, mean_free_path: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), mean_free_path)
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, mean_free_path: Color) -> Self {
+        Self {
+            albedo,
+            mean_free_path,
+            ior: 1.4,
+            roughness: 0.3,
+            g: 0.0,
+        }
+    }
+
+    /// Refractive index of the surface, 1.4 by default
+    pub fn with_ior(mut self, ior: f64) -> Self {
+        self.ior = ior;
+        self
+    }
+
+    /// Roughness [0..1] of the surface reflection, 0.3 by default
+    pub fn with_roughness(mut self, roughness: f64) -> Self {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Henyey-Greenstein anisotropy of the scattering inside, 0 by default
+    pub fn with_anisotropy(mut self, g: f64) -> Self {
+        self.g = g;
+        self
+    }
+
+    fn ggx(&self) -> Ggx {
+        Ggx::from_roughness(self.roughness, 0.0)
+    }
+
+    fn fresnel(&self, cos_theta: f64) -> f64 {
+        let r0 = ((self.ior - 1.0) / (self.ior + 1.0)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+    }
+
+    /// Probability of reflecting off the surface instead of entering it, zero when leaving
+    fn reflect_probability(&self, hit: &HitInfo, wo: Vec3) -> f64 {
+        if hit.front_face {
+            self.fresnel(wo.z())
+        } else {
+            0.0
+        }
+    }
+
+    /// BSDF times cosine in the frame of the facing normal. Light enters through the lower
+    /// hemisphere, leaving the inside it is always transmitted
+    fn eval_local(&self, hit: &HitInfo, wo: Vec3, wi: Vec3) -> Color {
+        if wo.z() <= 0.0 {
+            return Color::zero();
+        }
+        if wi.z() < 0.0 {
+            return Color::full((1.0 - self.reflect_probability(hit, wo)) * -wi.z() * FRAC_1_PI);
+        }
+        if !hit.front_face {
+            return Color::zero();
+        }
+        let h = (wo + wi).normalize();
+        let ggx = self.ggx();
+        Color::full(self.fresnel(wi.dot(h)) * ggx.d(h) * ggx.g2(wo, wi) / (4.0 * wo.z()))
+    }
+
+    fn pdf_local(&self, hit: &HitInfo, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        let reflect = self.reflect_probability(hit, wo);
+        if wi.z() < 0.0 {
+            (1.0 - reflect) * -wi.z() * FRAC_1_PI
+        } else {
+            reflect * self.ggx().reflection_pdf(wo, wi)
+        }
+    }
+}
+
+/// Single scattering albedo that gives roughly `albedo` after many scattering events, the fit
+/// of Chiang et al. 2016
+fn single_scattering_albedo(albedo: f64) -> f64 {
+    let a = albedo.clamp(0.0, 0.999);
+    1.0 - (a * (-5.09406 + a * (2.61188 - a * 4.31805))).exp()
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray: &Ray, hit: &HitInfo) -> Option<ScatterInfo> {
+        let frame = Onb::from_normal(hit.facing_normal());
+        let wo = frame.to_local(-ray.direction.normalize());
+        let u = (random(), random());
+        let wi = if random::<f64>() < self.reflect_probability(hit, wo) {
+            -wo.reflect(self.ggx().sample_visible_normal(wo, u))
+        } else {
+            // 拡散透過で面の反対側へ進む
+            -cosine_hemisphere(u)
+        };
+
+        let pdf = self.pdf_local(hit, wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterInfo::new(
+            Ray::new(hit.position, frame.to_world(wi)),
+            self.eval_local(hit, wo, wi) / pdf,
+        ))
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitInfo, direction: Vec3) -> Color {
+        let frame = Onb::from_normal(hit.facing_normal());
+        self.eval_local(
+            hit,
+            frame.to_local(-ray.direction.normalize()),
+            frame.to_local(direction),
+        )
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &HitInfo, direction: Vec3) -> f64 {
+        let frame = Onb::from_normal(hit.facing_normal());
+        self.pdf_local(
+            hit,
+            frame.to_local(-ray.direction.normalize()),
+            frame.to_local(direction),
+        )
+    }
+
+    fn subsurface(&self, hit: &HitInfo) -> Option<HomogeneousMedium> {
+        let albedo = texture_value(self.albedo.as_ref(), hit);
+        let single = Color::from_iter(albedo.iter().map(|&a| single_scattering_albedo(a)));
+        let sigma_t = Color::from_iter(self.mean_free_path.iter().map(|&d| d.max(1e-6).recip()));
+        Some(HomogeneousMedium::new(
+            sigma_t * (Color::one() - single),
+            sigma_t * single,
+            self.g,
+        ))
+    }
+}
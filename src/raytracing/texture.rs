@@ -0,0 +1,542 @@
+//! Textures giving materials a color that varies over the surface.
+//!
+//! Image textures are looked up with the texture coordinates of the hit, procedural ones
+//! with its world position.
+
+use std::path::Path;
+
+use image::{ColorType, ImageResult};
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+
+use super::{sampler, Color, Point3, PI2};
+
+pub trait Texture: Sync + Send {
+    /// Returns the color at texture coordinates (u, v) and world position `p`
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+}
+
+/// Texture with the same color everywhere
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.color
+    }
+}
+
+/// How texture coordinates outside [0..1] are mapped back into the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    /// Repeat, flipping every other copy
+    Mirror,
+    /// Extend the edge pixels
+    Clamp,
+}
+
+impl WrapMode {
+    fn wrap(self, i: isize, size: usize) -> usize {
+        let n = size as isize;
+        match self {
+            WrapMode::Repeat => i.rem_euclid(n) as usize,
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                (if i < n { i } else { 2 * n - 1 - i }) as usize
+            }
+            WrapMode::Clamp => i.clamp(0, n - 1) as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+/// Texture read from an image, v = 0 is the bottom row as in OBJ files
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    wrap: WrapMode,
+    filter: Filter,
+    /// Number of repetitions of the image along u and v
+    scale: (f64, f64),
+}
+
+impl ImageTexture {
+    /// Build from linear pixels stored row by row from the top
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        assert!(width > 0 && height > 0);
+        Self {
+            width,
+            height,
+            pixels,
+            wrap: WrapMode::default(),
+            filter: Filter::default(),
+            scale: (1.0, 1.0),
+        }
+    }
+
+    /// Load an image, 8 and 16-bit images are decoded with `gamma`.
+    ///
+    /// Use a gamma of 1 for images holding data rather than colors, such as roughness maps.
+    /// HDR and OpenEXR images are always linear.
+    pub fn load(path: impl AsRef<Path>, gamma: f64) -> ImageResult<Self> {
+        let image = image::open(path)?;
+        let linear = matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+        let image = image.into_rgb32f();
+        let pixels = image
+            .pixels()
+            .map(|p| {
+                let color = Color::new(p[0] as f64, p[1] as f64, p[2] as f64);
+                if linear {
+                    color
+                } else {
+                    color.degamma(gamma)
+                }
+            })
+            .collect();
+        Ok(Self::new(
+            image.width() as usize,
+            image.height() as usize,
+            pixels,
+        ))
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_scale(mut self, u: f64, v: f64) -> Self {
+        self.scale = (u, v);
+        self
+    }
+
+    fn texel(&self, x: isize, y: isize) -> Color {
+        let x = self.wrap.wrap(x, self.width);
+        let y = self.wrap.wrap(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        // 画像は上の行から並んでいるので v を反転する
+        let fx = u * self.scale.0 * self.width as f64;
+        let fy = (1.0 - v * self.scale.1) * self.height as f64;
+        match self.filter {
+            Filter::Nearest => self.texel(fx.floor() as isize, fy.floor() as isize),
+            Filter::Bilinear => {
+                // 画素中心を基準に補間する
+                let (fx, fy) = (fx - 0.5, fy - 0.5);
+                let (x, y) = (fx.floor(), fy.floor());
+                let (tx, ty) = (fx - x, fy - y);
+                let (x, y) = (x as isize, y as isize);
+                let top = self.texel(x, y).lerp(self.texel(x + 1, y), tx);
+                let bottom = self.texel(x, y + 1).lerp(self.texel(x + 1, y + 1), tx);
+                top.lerp(bottom, ty)
+            }
+        }
+    }
+}
+
+/// Coordinates a procedural pattern is laid out in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureSpace {
+    /// World position, the pattern is carved out of a solid block
+    #[default]
+    World,
+    /// Texture coordinates, the pattern follows the surface
+    Uv,
+}
+
+/// Alternating squares or cubes of two colors, `scale` is the number of cells per unit
+pub struct CheckerTexture {
+    even: Color,
+    odd: Color,
+    scale: f64,
+    space: TextureSpace,
+}
+
+impl CheckerTexture {
+    pub fn new(even: Color, odd: Color, scale: f64) -> Self {
+        Self {
+            even,
+            odd,
+            scale,
+            space: TextureSpace::default(),
+        }
+    }
+
+    pub fn with_space(mut self, space: TextureSpace) -> Self {
+        self.space = space;
+        self
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let cell = |x: f64| (x * self.scale).floor() as i64;
+        let parity = match self.space {
+            TextureSpace::World => cell(p.x()) + cell(p.y()) + cell(p.z()),
+            TextureSpace::Uv => cell(u) + cell(v),
+        };
+        if parity.rem_euclid(2) == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+/// Perlin's improved gradient noise
+pub struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(&mut SmallRng::seed_from_u64(seed));
+        let mut permutation = [0; 512];
+        for (i, p) in permutation.iter_mut().enumerate() {
+            *p = table[i % 256];
+        }
+        Self { permutation }
+    }
+
+    /// Noise in about [-1..1], zero at every lattice point
+    pub fn noise(&self, p: Point3) -> f64 {
+        let cell = [p.x().floor(), p.y().floor(), p.z().floor()];
+        let [x, y, z] = [p.x() - cell[0], p.y() - cell[1], p.z() - cell[2]];
+        let [i, j, k] = cell.map(|c| (c as i64).rem_euclid(256) as usize);
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let perm = &self.permutation;
+        let hash = |di: usize, dj: usize, dk: usize| {
+            perm[perm[perm[i + di] as usize + j + dj] as usize + k + dk]
+        };
+        let corner = |di: usize, dj: usize, dk: usize| {
+            grad(
+                hash(di, dj, dk),
+                x - di as f64,
+                y - dj as f64,
+                z - dk as f64,
+            )
+        };
+        let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    /// Fractal Brownian motion, octaves of noise doubling in frequency and halving in
+    /// amplitude. In about [-1..1]
+    pub fn fbm(&self, p: Point3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, |n| n)
+    }
+
+    /// Like `fbm` but sums the absolute value of each octave, in [0..1]
+    pub fn turbulence(&self, p: Point3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, f64::abs)
+    }
+
+    fn octaves(&self, p: Point3, octaves: u32, f: impl Fn(f64) -> f64) -> f64 {
+        let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0);
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * f(self.noise(p * frequency));
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        sum / total
+    }
+}
+
+/// Dot product of the offset with one of the 12 cube edge directions picked by `hash`
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Cellular (Worley) noise from one random feature point per unit cell
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Distances to the nearest and the second nearest feature points
+    pub fn distances(&self, p: Point3) -> (f64, f64) {
+        let cell = [p.x().floor(), p.y().floor(), p.z().floor()].map(|c| c as i64);
+        let (mut f1, mut f2) = (f64::MAX, f64::MAX);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let [x, y, z] = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+                    let offset = |axis: u64| {
+                        sampler::to_unit(sampler::hash(&[
+                            x as u64, y as u64, z as u64, self.seed, axis,
+                        ]))
+                    };
+                    let feature = Point3::new(
+                        x as f64 + offset(0),
+                        y as f64 + offset(1),
+                        z as f64 + offset(2),
+                    );
+                    let d = (feature - p).length();
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+        (f1, f2)
+    }
+}
+
+/// Which distance of the Worley noise drives `WorleyTexture`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorleyFeature {
+    /// Distance to the nearest point, round cells
+    #[default]
+    F1,
+    /// Difference of the two nearest distances, dark lines along the cell borders
+    Edge,
+}
+
+/// Blends two colors by fBm noise, `scale` is the base frequency
+pub struct NoiseTexture {
+    perlin: Perlin,
+    scale: f64,
+    octaves: u32,
+    colors: (Color, Color),
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            perlin: Perlin::new(0),
+            scale,
+            octaves: 6,
+            colors: (Color::zero(), Color::one()),
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn with_colors(mut self, low: Color, high: Color) -> Self {
+        self.colors = (low, high);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.perlin = Perlin::new(seed);
+        self
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let n = self.perlin.fbm(p * self.scale, self.octaves);
+        self.colors
+            .0
+            .lerp(self.colors.1, (0.5 * (n + 1.0)).clamp(0.0, 1.0))
+    }
+}
+
+/// Blends two colors by cellular noise, `scale` is the number of cells per unit
+pub struct WorleyTexture {
+    worley: Worley,
+    scale: f64,
+    feature: WorleyFeature,
+    colors: (Color, Color),
+}
+
+impl WorleyTexture {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            worley: Worley::new(0),
+            scale,
+            feature: WorleyFeature::default(),
+            colors: (Color::zero(), Color::one()),
+        }
+    }
+
+    pub fn with_feature(mut self, feature: WorleyFeature) -> Self {
+        self.feature = feature;
+        self
+    }
+
+    pub fn with_colors(mut self, low: Color, high: Color) -> Self {
+        self.colors = (low, high);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.worley = Worley::new(seed);
+        self
+    }
+}
+
+impl Texture for WorleyTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let (f1, f2) = self.worley.distances(p * self.scale);
+        let t = match self.feature {
+            WorleyFeature::F1 => f1,
+            // 境界付近で 0 になるので、線が細くなるように引き伸ばす
+            WorleyFeature::Edge => 4.0 * (f2 - f1),
+        };
+        self.colors.0.lerp(self.colors.1, t.clamp(0.0, 1.0))
+    }
+}
+
+/// Veined stone, stripes along x bent by turbulence. `scale` is the number of veins per unit
+pub struct MarbleTexture {
+    perlin: Perlin,
+    scale: f64,
+    turbulence: f64,
+    octaves: u32,
+    colors: (Color, Color),
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            perlin: Perlin::new(0),
+            scale,
+            turbulence: 5.0,
+            octaves: 6,
+            colors: (Color::new(0.2, 0.2, 0.25), Color::new(0.9, 0.9, 0.88)),
+        }
+    }
+
+    /// Strength with which the veins are distorted
+    pub fn with_turbulence(mut self, turbulence: f64) -> Self {
+        self.turbulence = turbulence;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    /// `vein` is the color of the veins and `base` the color between them
+    pub fn with_colors(mut self, vein: Color, base: Color) -> Self {
+        self.colors = (vein, base);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.perlin = Perlin::new(seed);
+        self
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let p = p * self.scale;
+        let phase = PI2 * p.x() + self.turbulence * self.perlin.turbulence(p, self.octaves);
+        // 正弦波の谷を細くして筋に見せる
+        let t = (0.5 * (1.0 + phase.sin())).sqrt();
+        self.colors.0.lerp(self.colors.1, t)
+    }
+}
+
+/// Growth rings around the y axis, `scale` is the number of rings per unit
+pub struct WoodTexture {
+    perlin: Perlin,
+    scale: f64,
+    turbulence: f64,
+    colors: (Color, Color),
+}
+
+impl WoodTexture {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            perlin: Perlin::new(0),
+            scale,
+            turbulence: 0.3,
+            colors: (Color::new(0.3, 0.15, 0.06), Color::new(0.6, 0.38, 0.18)),
+        }
+    }
+
+    /// Strength with which the rings are distorted, in rings
+    pub fn with_turbulence(mut self, turbulence: f64) -> Self {
+        self.turbulence = turbulence;
+        self
+    }
+
+    /// `ring` is the color of the late wood and `base` of the early wood
+    pub fn with_colors(mut self, ring: Color, base: Color) -> Self {
+        self.colors = (ring, base);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.perlin = Perlin::new(seed);
+        self
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let p = p * self.scale;
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        // 年輪の間隔を少し揺らす。縦方向には引き伸ばして木目にする
+        let grain = Point3::new(p.x(), p.y() * 0.1, p.z());
+        let rings = radius + self.turbulence * self.perlin.fbm(grain, 4);
+        let t = rings - rings.floor();
+        // 明るい早材からゆっくり暗くなり、輪の境目で急に明るさが戻る
+        let t = (t / 0.2).min((1.0 - t) / 0.8).clamp(0.0, 1.0);
+        self.colors.0.lerp(self.colors.1, t)
+    }
+}
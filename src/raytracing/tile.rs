@@ -0,0 +1,100 @@
+/// Rectangular region of the image rendered as one unit of work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Order in which tiles are handed out to the render threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// Left to right, top to bottom
+    Scanline,
+    /// From the center of the image outwards
+    #[default]
+    Spiral,
+    /// Along a Hilbert curve, which keeps neighbouring tiles close in time
+    Hilbert,
+}
+
+impl std::str::FromStr for TileOrder {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "scanline" => Ok(TileOrder::Scanline),
+            "spiral" => Ok(TileOrder::Spiral),
+            "hilbert" => Ok(TileOrder::Hilbert),
+            _ => Err(format!(
+                "unknown tile order `{}`, expected one of scanline, spiral, hilbert",
+                s
+            )),
+        }
+    }
+}
+
+/// Split a `width` x `height` image into tiles of `tile_size` pixels in the given order
+pub fn tiles(width: u32, height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let tiles_x = width.div_ceil(tile_size);
+    let tiles_y = height.div_ceil(tile_size);
+
+    let mut coords: Vec<(u32, u32)> = (0..tiles_y)
+        .flat_map(|ty| (0..tiles_x).map(move |tx| (tx, ty)))
+        .collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            // 中心からの距離(チェビシェフ距離)ごとのリングを角度順に並べる
+            let cx = (tiles_x as f64 - 1.0) * 0.5;
+            let cy = (tiles_y as f64 - 1.0) * 0.5;
+            let key = |&(tx, ty): &(u32, u32)| {
+                let dx = tx as f64 - cx;
+                let dy = ty as f64 - cy;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            coords.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+        TileOrder::Hilbert => {
+            let n = tiles_x.max(tiles_y).next_power_of_two();
+            coords.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+        }
+    }
+
+    coords
+        .into_iter()
+        .map(|(tx, ty)| {
+            let x = tx * tile_size;
+            let y = ty * tile_size;
+            Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            }
+        })
+        .collect()
+}
+
+/// Distance of (x, y) along the Hilbert curve filling a `n` x `n` grid
+fn hilbert_index(n: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut d = 0u64;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        // 象限に合わせて座標を回転させる
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
@@ -0,0 +1,151 @@
+use image::{Rgb, RgbImage};
+
+use super::{film::Film, sampler, Color};
+
+/// Curve compressing linear HDR values into the displayable range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapOperator {
+    /// Clip at 1.0
+    Clamp,
+    /// Reinhard applied to luminance, keeps hue
+    Reinhard,
+    /// John Hable's filmic curve (Uncharted 2)
+    Hable,
+    /// Krzysztof Narkowicz / Stephen Hill fit of the ACES reference rendering transform
+    #[default]
+    Aces,
+}
+
+impl std::str::FromStr for ToneMapOperator {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clamp" | "none" => Ok(ToneMapOperator::Clamp),
+            "reinhard" => Ok(ToneMapOperator::Reinhard),
+            "hable" | "filmic" => Ok(ToneMapOperator::Hable),
+            "aces" => Ok(ToneMapOperator::Aces),
+            _ => Err(format!(
+                "unknown tone mapping operator `{}`, expected one of clamp, reinhard, hable, aces",
+                s
+            )),
+        }
+    }
+}
+
+/// Post-process turning the linear film into an 8-bit sRGB image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToneMapper {
+    /// Exposure adjustment in stops
+    pub exposure: f64,
+    pub operator: ToneMapOperator,
+    /// Add triangular noise of one code value before quantizing to hide banding
+    pub dither: bool,
+}
+
+impl Default for ToneMapper {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            operator: ToneMapOperator::default(),
+            dither: true,
+        }
+    }
+}
+
+impl ToneMapper {
+    pub fn new(operator: ToneMapOperator) -> Self {
+        Self {
+            operator,
+            ..Default::default()
+        }
+    }
+
+    pub fn exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Map a linear scene value to a linear display value in [0..1]
+    pub fn map(&self, color: Color) -> Color {
+        let c = color * self.exposure.exp2();
+        match self.operator {
+            ToneMapOperator::Clamp => c.saturate(),
+            ToneMapOperator::Reinhard => {
+                let l = c.luminance();
+                if l <= 0.0 {
+                    Color::zero()
+                } else {
+                    (c * (1.0 / (1.0 + l))).saturate()
+                }
+            }
+            ToneMapOperator::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                let white_scale = hable(WHITE).recip();
+                Color::from_iter(c.iter().map(|&x| hable(x * EXPOSURE_BIAS) * white_scale))
+                    .saturate()
+            }
+            ToneMapOperator::Aces => aces_fitted(c),
+        }
+    }
+
+    /// Apply exposure, the tone curve and the sRGB transfer function to a film
+    pub fn apply(&self, film: &Film) -> RgbImage {
+        RgbImage::from_fn(film.width(), film.height(), |x, y| {
+            let srgb = self.map(film.get(x, y)).to_srgb();
+            let noise = if self.dither {
+                // 画素ごとに決まった三角分布のノイズ (-1..1 LSB)
+                let h = sampler::hash(&[x as u64, y as u64]);
+                let a = (h >> 40) as f64 / (1u64 << 24) as f64;
+                let b = (h & 0xff_ffff) as f64 / (1u64 << 24) as f64;
+                (a + b - 1.0) / 255.0
+            } else {
+                0.0
+            };
+            Rgb((srgb + Color::full(noise)).to_rgb())
+        })
+    }
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+/// ACES fitted curve with the sRGB -> ACEScg -> sRGB matrices folded in
+fn aces_fitted(c: Color) -> Color {
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let mul = |m: &[[f64; 3]; 3], v: Color| {
+        Color::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    };
+    let v = mul(&INPUT, c);
+    let rrt_odt = Color::from_iter(v.iter().map(|&x| {
+        let a = x * (x + 0.0245786) - 0.000090537;
+        let b = x * (0.983729 * x + 0.432951) + 0.238081;
+        a / b
+    }));
+    mul(&OUTPUT, rrt_odt).saturate()
+}
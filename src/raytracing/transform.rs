@@ -0,0 +1,310 @@
+//! Affine transforms and instanced shapes.
+
+use std::sync::Arc;
+
+use super::{
+    aabb::Aabb, csg::Span, hit_info::HitInfo, material::Material, quaternion::Quaternion, ray::Ray,
+    shapes::Shape, Point3, Vec3,
+};
+
+/// Row-major 4x4 matrix, vectors are columns multiplied on the right
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4(pub [[f64; 4]; 4]);
+
+impl Matrix4 {
+    pub const fn identity() -> Self {
+        Matrix4([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let m = &self.0;
+        Matrix4(std::array::from_fn(|i| std::array::from_fn(|j| m[j][i])))
+    }
+
+    /// Compute the inverse by Gauss-Jordan elimination, None if the matrix is singular
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.0;
+        let mut inv = Self::identity().0;
+        for col in 0..4 {
+            // 絶対値が最大の行を軸に選んで誤差を抑える
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let recip = a[col][col].recip();
+            for j in 0..4 {
+                a[col][j] *= recip;
+                inv[col][j] *= recip;
+            }
+            for row in (0..4).filter(|&row| row != col) {
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+        Some(Matrix4(inv))
+    }
+
+    /// Multiply a point, applying the translation
+    pub fn point(&self, p: Point3) -> Point3 {
+        let m = &self.0;
+        let [x, y, z] = p.to_array();
+        let w = m[3][0] * x + m[3][1] * y + m[3][2] * z + m[3][3];
+        let q = Point3::new(
+            m[0][0] * x + m[0][1] * y + m[0][2] * z + m[0][3],
+            m[1][0] * x + m[1][1] * y + m[1][2] * z + m[1][3],
+            m[2][0] * x + m[2][1] * y + m[2][2] * z + m[2][3],
+        );
+        if w == 1.0 {
+            q
+        } else {
+            q / w
+        }
+    }
+
+    /// Multiply a direction, ignoring the translation
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.0;
+        let [x, y, z] = v.to_array();
+        Vec3::new(
+            m[0][0] * x + m[0][1] * y + m[0][2] * z,
+            m[1][0] * x + m[1][1] * y + m[1][2] * z,
+            m[2][0] * x + m[2][1] * y + m[2][2] * z,
+        )
+    }
+}
+
+impl std::ops::Mul<Matrix4> for Matrix4 {
+    type Output = Self;
+    fn mul(self, rhs: Matrix4) -> Self {
+        let (a, b) = (&self.0, &rhs.0);
+        Matrix4(std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..4).map(|k| a[i][k] * b[k][j]).sum())
+        }))
+    }
+}
+
+/// Invertible affine transform, stored together with its inverse.
+///
+/// `a * b` applies `b` first and then `a`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub const fn identity() -> Self {
+        Self {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    /// Construct from a matrix, None if it cannot be inverted
+    pub fn from_matrix(matrix: Matrix4) -> Option<Self> {
+        Some(Self {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        let [x, y, z] = offset.to_array();
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for (i, d) in [x, y, z].into_iter().enumerate() {
+            matrix.0[i][3] = d;
+            inverse.0[i][3] = -d;
+        }
+        Self { matrix, inverse }
+    }
+
+    /// Scale along each axis, None if a factor is zero
+    pub fn scale(factors: Vec3) -> Option<Self> {
+        if factors.iter().any(|&f| f == 0.0) {
+            return None;
+        }
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for (i, &f) in factors.iter().enumerate() {
+            matrix.0[i][i] = f;
+            inverse.0[i][i] = f.recip();
+        }
+        Some(Self { matrix, inverse })
+    }
+
+    pub fn rotate(rotation: Quaternion) -> Self {
+        let [x, y, z, w] = rotation.normalize().to_array();
+        let matrix = Matrix4([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        // 回転行列の逆行列は転置
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// Scale, then rotate, then translate
+    pub fn from_trs(translation: Vec3, rotation: Quaternion, scale: Vec3) -> Option<Self> {
+        Some(Self::translate(translation) * Self::rotate(rotation) * Self::scale(scale)?)
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.vector(v)
+    }
+
+    /// Transform a normal with the inverse transpose so it stays perpendicular to the surface.
+    /// The result is not normalized
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().vector(n)
+    }
+
+    /// Transform both ends of a ray, the distances along it are unchanged
+    pub fn ray(&self, ray: &Ray) -> Ray {
+        Ray::new(self.point(ray.origin), self.vector(ray.direction))
+    }
+
+    /// Box containing the transformed corners of `aabb`
+    pub fn bounds(&self, aabb: Aabb) -> Aabb {
+        Aabb::from_points((0..8).map(|i| {
+            let corner = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    aabb.min[axis]
+                } else {
+                    aabb.max[axis]
+                }
+            };
+            self.point(Point3::new(corner(0), corner(1), corner(2)))
+        }))
+    }
+}
+
+impl std::ops::Mul<Transform> for Transform {
+    type Output = Self;
+    fn mul(self, rhs: Transform) -> Self {
+        Self {
+            matrix: self.matrix * rhs.matrix,
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
+
+/// Places a shared shape in the scene with a transform.
+///
+/// The geometry is referenced by `Arc`, so any number of instances of one mesh cost only
+/// the transform each.
+pub struct Instance {
+    shape: Arc<dyn Shape>,
+    transform: Transform,
+    /// Replaces the materials of the shape when given
+    material: Option<Arc<dyn Material>>,
+    bounds: Option<Aabb>,
+}
+
+impl Instance {
+    pub fn new(shape: Arc<dyn Shape>, transform: Transform) -> Self {
+        let bounds = shape.bounding_box().map(|b| transform.bounds(b));
+        Self {
+            shape,
+            transform,
+            material: None,
+            bounds,
+        }
+    }
+
+    pub fn with_material(mut self, material: Arc<dyn Material>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// Move a hit on the shape back into the world
+    fn to_world(&self, ray: &Ray, mut hit: HitInfo) -> HitInfo {
+        hit.position = ray.at(hit.length);
+        hit.direction = self.transform.normal(hit.direction).normalize();
+        hit.dpdu = self.transform.vector(hit.dpdu);
+        hit.dpdv = self.transform.vector(hit.dpdv);
+        if let Some(material) = &self.material {
+            hit.material = Arc::clone(material);
+        }
+        hit
+    }
+}
+
+impl Shape for Instance {
+    fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo> {
+        // レイを物体の座標系に移す。方向は正規化しないので距離はそのまま使える
+        let local = self.transform.inverse().ray(ray);
+        let hit = self.shape.hit(&local, t0, t1)?;
+        Some(self.to_world(ray, hit))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    fn intervals(&self, ray: &Ray, t0: f64, t1: f64) -> Vec<Span> {
+        let local = self.transform.inverse().ray(ray);
+        let mut spans = self.shape.intervals(&local, t0, t1);
+        for span in &mut spans {
+            for boundary in [&mut span.enter, &mut span.exit] {
+                boundary.hit = boundary.hit.take().map(|hit| self.to_world(ray, hit));
+            }
+        }
+        spans
+    }
+}
@@ -1,29 +1,20 @@
-use std::{thread, vec};
-
 use image::RgbImage;
-use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use minifb::{Key, Window, WindowOptions};
 
-pub struct Draw {
-    window_image: Vec<u32>,
-}
+pub struct Draw;
 
 impl Draw {
     pub fn new() -> Self {
-        Self {
-            window_image: Vec::new(),
-        }
+        Self
     }
 
     fn window_update(
         &self,
         window: &mut Window,
-        window_buffer: &mut Vec<u32>,
+        window_buffer: &[u32],
         image_width: usize,
         image_height: usize,
     ) -> minifb::Result<()> {
-        // Dキーが押されたら
-        if window.is_key_pressed(Key::D, KeyRepeat::No) {}
-
         window.update_with_buffer(window_buffer, image_width, image_height)?;
 
         Ok(())
@@ -36,7 +27,7 @@ impl Draw {
 
         let (image_width, image_height) = window_image.dimensions();
         let mut window = Window::new(
-            "Esc: exit. D: ",
+            "Esc: exit",
             image_width as usize,
             image_height as usize,
             WindowOptions {
@@ -47,7 +38,7 @@ impl Draw {
         .unwrap();
 
         // ~30fps までにリミットする
-        window.limit_update_rate(Some(std::time::Duration::from_micros(16600 * 2)));
+        window.set_target_fps(30);
 
         let mut window_buffer: Vec<u32> = vec![0; (image_width * image_height) as usize];
 
@@ -61,7 +52,7 @@ impl Draw {
         while window.is_open() && !window.is_key_down(Key::Escape) {
             self.window_update(
                 &mut window,
-                &mut window_buffer,
+                &window_buffer,
                 image_width as usize,
                 image_height as usize,
             )?;